@@ -1,55 +1,188 @@
-use crate::value::Value;
+use std::fmt::Write;
 
 use super::{Chunk, OpCode};
+use crate::value::Value;
 use OpCode::*;
 
-pub fn disassemble_chunk(chunk: &Chunk, name: &str) {
-    println!("== {} ==", name);
-    let mut offset = 0;
-    while offset < chunk.count {
-        offset = disassemble_instruction(chunk, offset);
-    }
-    println!("== {} ==", name);
+/// 把字节码翻译成可读的文本; 常量默认按 `Debug` 显示, 有堆时可以传入更友好的格式化函数
+pub struct Disassembler<'a> {
+    chunk: &'a Chunk,
+    format_value: &'a dyn Fn(Value) -> String,
+    out: String,
 }
-
-pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
-    let byte = &chunk.code[offset];
-    match byte {
-        OPCONSTANT => constant_instruction("OPCONSTANT", chunk, offset),
-        OPNIL => simple_instruction("OPNIL", offset),
-        OPTRUE => simple_instruction("OPTRUE", offset),
-        OPFALSE => simple_instruction("OPFALSE", offset),
-        OPRETURN => simple_instruction("OPRETURN", offset),
-        OPEQUAL => simple_instruction("OPEQUAL", offset),
-        OPGREATER => simple_instruction("OPGREATER", offset),
-        OPLESS => simple_instruction("OPLESS", offset),
-        OPADD => simple_instruction("OPADD", offset),
-        OPSUBTRACT => simple_instruction("OPSUBTRACT", offset),
-        OPMULTIPLY => simple_instruction("OPMULTIPLY", offset),
-        OPDIVIDE => simple_instruction("OPDIVIDE", offset),
-        OPNOT => simple_instruction("OPNOT", offset),
-        OPNEGATE => simple_instruction("OPNEGATE", offset),
-        _ => todo!(),
+impl<'a> Disassembler<'a> {
+    pub fn new(chunk: &'a Chunk, format_value: &'a dyn Fn(Value) -> String) -> Self {
+        Self {
+            chunk,
+            format_value,
+            out: String::new(),
+        }
+    }
+    pub fn chunk(mut self, name: &str) -> String {
+        let _ = writeln!(self.out, "== {} ==", name);
+        let mut offset = 0;
+        while offset < self.chunk.count {
+            offset = self.instruction(offset);
+        }
+        let _ = writeln!(self.out, "== {} ==", name);
+        self.out
+    }
+    /// 反汇编 `offset` 处的一条指令, 返回下一条指令的偏移
+    pub fn instruction(&mut self, offset: usize) -> usize {
+        let chunk = self.chunk;
+        // 与上一条指令同一行时只画一条竖线
+        let line = chunk.line_at(offset);
+        if offset > 0 && line == chunk.line_at(offset - 1) {
+            let _ = write!(self.out, "{:04}    | ", offset);
+        } else {
+            let _ = write!(self.out, "{:04} {:4} ", offset, line);
+        }
+        let byte = chunk.code[offset];
+        let Ok(op) = OpCode::try_from(byte) else {
+            let _ = writeln!(self.out, "unknown opcode {}", byte);
+            return offset + 1;
+        };
+        match op {
+            OPCONSTANT => self.constant_instruction("OPCONSTANT", offset),
+            OPCONSTANT_LONG => self.constant_long_instruction("OPCONSTANT_LONG", offset),
+            OPNIL => self.simple_instruction("OPNIL", offset),
+            OPTRUE => self.simple_instruction("OPTRUE", offset),
+            OPFALSE => self.simple_instruction("OPFALSE", offset),
+            OPRETURN => self.simple_instruction("OPRETURN", offset),
+            OPEQUAL => self.simple_instruction("OPEQUAL", offset),
+            OPGREATER => self.simple_instruction("OPGREATER", offset),
+            OPLESS => self.simple_instruction("OPLESS", offset),
+            OPADD => self.simple_instruction("OPADD", offset),
+            OPSUBTRACT => self.simple_instruction("OPSUBTRACT", offset),
+            OPMULTIPLY => self.simple_instruction("OPMULTIPLY", offset),
+            OPDIVIDE => self.simple_instruction("OPDIVIDE", offset),
+            OPNOT => self.simple_instruction("OPNOT", offset),
+            OPNEGATE => self.simple_instruction("OPNEGATE", offset),
+            OPPRINT => self.simple_instruction("OPPRINT", offset),
+            OPPOP => self.simple_instruction("OPPOP", offset),
+            OPDEFINE_GLOBAL => self.constant_instruction("OPDEFINE_GLOBAL", offset),
+            OPGET_GLOBAL => self.constant_instruction("OPGET_GLOBAL", offset),
+            OPSET_GLOBAL => self.constant_instruction("OPSET_GLOBAL", offset),
+            OPGET_LOCAL => self.byte_instruction("OPGET_LOCAL", offset),
+            OPSET_LOCAL => self.byte_instruction("OPSET_LOCAL", offset),
+            OPJUMP => self.jump_instruction("OPJUMP", true, offset),
+            OPJUMP_IF_FALSE => self.jump_instruction("OPJUMP_IF_FALSE", true, offset),
+            OPLOOP => self.jump_instruction("OPLOOP", false, offset),
+            OPCALL => self.byte_instruction("OPCALL", offset),
+            OPCLOSURE => self.closure_instruction(offset),
+            OPGET_UPVALUE => self.byte_instruction("OPGET_UPVALUE", offset),
+            OPSET_UPVALUE => self.byte_instruction("OPSET_UPVALUE", offset),
+            OPCLOSE_UPVALUE => self.simple_instruction("OPCLOSE_UPVALUE", offset),
+            OPCLASS => self.constant_instruction("OPCLASS", offset),
+            OPGET_PROPERTY => self.constant_instruction("OPGET_PROPERTY", offset),
+            OPSET_PROPERTY => self.constant_instruction("OPSET_PROPERTY", offset),
+            OPMETHOD => self.constant_instruction("OPMETHOD", offset),
+            OPINVOKE => self.invoke_instruction("OPINVOKE", offset),
+            OPINHERIT => self.simple_instruction("OPINHERIT", offset),
+            OPGET_SUPER => self.constant_instruction("OPGET_SUPER", offset),
+            OPSUPER_INVOKE => self.invoke_instruction("OPSUPER_INVOKE", offset),
+            OPDEFINE_GLOBAL_LONG => self.constant_long_instruction("OPDEFINE_GLOBAL_LONG", offset),
+            OPGET_GLOBAL_LONG => self.constant_long_instruction("OPGET_GLOBAL_LONG", offset),
+            OPSET_GLOBAL_LONG => self.constant_long_instruction("OPSET_GLOBAL_LONG", offset),
+            OPCLOSURE_LONG => self.closure_instruction(offset),
+            OPCLASS_LONG => self.constant_long_instruction("OPCLASS_LONG", offset),
+            OPGET_PROPERTY_LONG => self.constant_long_instruction("OPGET_PROPERTY_LONG", offset),
+            OPSET_PROPERTY_LONG => self.constant_long_instruction("OPSET_PROPERTY_LONG", offset),
+            OPMETHOD_LONG => self.constant_long_instruction("OPMETHOD_LONG", offset),
+            OPINVOKE_LONG => self.invoke_instruction("OPINVOKE_LONG", offset),
+            OPGET_SUPER_LONG => self.constant_long_instruction("OPGET_SUPER_LONG", offset),
+            OPSUPER_INVOKE_LONG => self.invoke_instruction("OPSUPER_INVOKE_LONG", offset),
+        }
+    }
+    /// 取出已经写入的文本
+    pub fn take(&mut self) -> String {
+        std::mem::take(&mut self.out)
+    }
+    fn simple_instruction(&mut self, name: &str, offset: usize) -> usize {
+        let _ = writeln!(self.out, "{}", name);
+        offset + 1
+    }
+    fn byte_instruction(&mut self, name: &str, offset: usize) -> usize {
+        let slot = self.chunk.code[offset + 1];
+        let _ = writeln!(self.out, "{:16} {}", name, slot);
+        offset + 2
+    }
+    fn jump_instruction(&mut self, name: &str, forward: bool, offset: usize) -> usize {
+        let jump = self.chunk.read_short(offset + 1);
+        let target = if forward {
+            offset + 3 + jump
+        } else {
+            offset + 3 - jump
+        };
+        let _ = writeln!(self.out, "{:16} {:04} -> {:04}", name, offset, target);
+        offset + 3
+    }
+    /// 读取 `offset` 处指令的常量下标, 返回下标和它之后的偏移
+    fn index(&self, offset: usize) -> (usize, usize) {
+        if OpCode::try_from(self.chunk.code[offset]).is_ok_and(OpCode::is_long) {
+            (self.chunk.read_long(offset + 1), offset + 4)
+        } else {
+            (self.chunk.code[offset + 1] as usize, offset + 2)
+        }
+    }
+    fn closure_instruction(&mut self, offset: usize) -> usize {
+        let (idx, next) = self.index(offset);
+        let count = self.chunk.read_short(next);
+        let constant = (self.format_value)(self.chunk.constants[idx]);
+        let name = if next == offset + 2 { "OPCLOSURE" } else { "OPCLOSURE_LONG" };
+        let _ = writeln!(self.out, "{:16} {}", name, constant);
+        let mut offset = next + 2;
+        for _ in 0..count {
+            let is_local = self.chunk.code[offset] == 1;
+            let index = self.chunk.code[offset + 1];
+            let kind = if is_local { "local" } else { "upvalue" };
+            let _ = writeln!(self.out, "{:04}    | {:16} {} {}", offset, "", kind, index);
+            offset += 2;
+        }
+        offset
+    }
+    fn invoke_instruction(&mut self, name: &str, offset: usize) -> usize {
+        let (idx, next) = self.index(offset);
+        let arg_count = self.chunk.code[next];
+        let constant = (self.format_value)(self.chunk.constants[idx]);
+        let _ = writeln!(self.out, "{:16} ({} args) {}", name, arg_count, constant);
+        next + 1
+    }
+    fn constant_instruction(&mut self, name: &str, offset: usize) -> usize {
+        let idx = self.chunk.code[offset + 1] as usize;
+        let constant = (self.format_value)(self.chunk.constants[idx]);
+        let _ = writeln!(self.out, "{:16} {}", name, constant);
+        offset + 2
+    }
+    fn constant_long_instruction(&mut self, name: &str, offset: usize) -> usize {
+        let idx = self.chunk.read_long(offset + 1);
+        let constant = (self.format_value)(self.chunk.constants[idx]);
+        let _ = writeln!(self.out, "{:16} {}", name, constant);
+        offset + 4
     }
 }
-fn simple_instruction(name: &str, offset: usize) -> usize {
-    println!("{:04}   {:16}", offset, name);
-    offset + 1
+
+fn debug_value(value: Value) -> String {
+    format!("{:?}", value)
 }
-fn constant_instruction(name: &str, chunk: &Chunk, offset: usize) -> usize {
-    let const_idx = chunk.code.get(offset + 1).expect("const_idx");
-    let idx = const_idx.as_value_idx();
-    let constant = chunk.constants[idx];
-    println!("{:04}   {:16} {:?}", offset, name, constant);
-    offset + 2
+
+pub fn disassemble_chunk(chunk: &Chunk, name: &str) {
+    print!("{}", Disassembler::new(chunk, &debug_value).chunk(name));
 }
+
 #[test]
 fn test() {
     let mut chunk = Chunk::new();
     chunk.write_constant(Value::Number(1.0), 1);
     chunk.write_chunk(OPNIL, 1);
-    chunk.write_chunk(OPTRUE, 1);
-    chunk.write_chunk(OPFALSE, 1);
-    chunk.write_chunk(OPRETURN, 1);
-    disassemble_chunk(&chunk, "test");
+    chunk.write_chunk(OPTRUE, 2);
+    chunk.write_chunk(OPRETURN, 2);
+    let text = Disassembler::new(&chunk, &debug_value).chunk("test");
+    let expected = "== test ==\n\
+                    0000    1 OPCONSTANT       Number(1.0)\n\
+                    0002    | OPNIL\n\
+                    0003    2 OPTRUE\n\
+                    0004    | OPRETURN\n\
+                    == test ==\n";
+    assert_eq!(text, expected);
 }
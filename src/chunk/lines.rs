@@ -0,0 +1,51 @@
+/// 一段连续来自同一源码行的字节
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineRun {
+    /// 这一段第一个字节在 `code` 中的偏移
+    pub start: usize,
+    pub line: usize,
+}
+
+/// 游程编码的行号表: 同一行生成的连续字节只记录一次
+#[derive(Debug, Default)]
+pub struct LineTable {
+    runs: Vec<LineRun>,
+}
+impl LineTable {
+    pub fn new() -> Self {
+        Self { runs: vec![] }
+    }
+    /// 由已经排好序的游程构造, 用于从字节码文件中加载
+    pub fn from_runs(runs: Vec<LineRun>) -> Self {
+        Self { runs }
+    }
+    /// 记录偏移 `offset` 处的字节来自 `line`; 偏移必须按写入顺序递增
+    pub fn push(&mut self, offset: usize, line: usize) {
+        match self.runs.last() {
+            Some(run) if run.line == line => {}
+            _ => self.runs.push(LineRun { start: offset, line }),
+        }
+    }
+    /// 返回偏移 `offset` 处的字节所在的源码行
+    pub fn line_at(&self, offset: usize) -> usize {
+        let idx = self.runs.partition_point(|run| run.start <= offset);
+        match idx {
+            0 => 0,
+            _ => self.runs[idx - 1].line,
+        }
+    }
+    pub fn runs(&self) -> &[LineRun] {
+        &self.runs
+    }
+}
+#[test]
+fn test() {
+    let mut table = LineTable::new();
+    for (offset, line) in [1, 1, 1, 2, 2, 4, 1].into_iter().enumerate() {
+        table.push(offset, line);
+    }
+    assert_eq!(table.runs().len(), 4);
+    let lines: Vec<usize> = (0..7).map(|offset| table.line_at(offset)).collect();
+    assert_eq!(lines, [1, 1, 1, 2, 2, 4, 1]);
+    assert_eq!(table.line_at(100), 1);
+}
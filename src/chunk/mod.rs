@@ -1,12 +1,21 @@
 pub mod debug;
+mod lines;
 mod opcode;
+pub use lines::*;
 pub use opcode::*;
 pub use OpCode::*;
 
 use crate::value::Value;
+
+/// 单字节常量下标能寻址的最大下标, 超出后改用指令的 `_LONG` 版本
+pub const CONSTANT_SHORT_MAX: usize = u8::MAX as usize;
+/// `_LONG` 指令的 24 位常量下标能寻址的最大下标
+pub const CONSTANT_LONG_MAX: usize = (1 << 24) - 1;
+
+#[derive(Debug)]
 pub struct Chunk {
-    pub code: Vec<OpCode>,
-    lines: Vec<usize>,
+    pub code: Vec<u8>,
+    pub lines: LineTable,
     count: usize,
     pub constants: Vec<Value>,
 }
@@ -14,26 +23,58 @@ impl Chunk {
     pub fn new() -> Self {
         Self {
             code: vec![],
-            lines: vec![],
+            lines: LineTable::new(),
             count: 0,
             constants: vec![],
         }
     }
+    pub fn from_parts(code: Vec<u8>, lines: LineTable, constants: Vec<Value>) -> Self {
+        Self {
+            count: code.len(),
+            code,
+            lines,
+            constants,
+        }
+    }
     pub fn disassemble(&self, name: &str) {
         debug::disassemble_chunk(self, name);
     }
-    pub fn write_chunk(&mut self, byte: OpCode, line: usize) {
-        self.code.push(byte);
-        self.lines.push(line);
+    pub fn write_chunk(&mut self, byte: impl Into<u8>, line: usize) {
+        self.lines.push(self.code.len(), line);
+        self.code.push(byte.into());
         self.count += 1;
     }
+    /// 写入一条加载常量的指令
     pub fn write_constant(&mut self, value: Value, line: usize) {
         let idx = self.add_constant(value);
-        self.write_chunk(OPCONSTANT, line);
-        self.write_chunk(OPVALUEIDX(idx), line);
+        self.write_indexed(OPCONSTANT, idx, line);
+    }
+    /// 写入一条以常量下标为操作数的指令, 下标超过一个字节时改用它的 `_LONG` 版本
+    pub fn write_indexed(&mut self, op: OpCode, idx: usize, line: usize) {
+        if idx <= CONSTANT_SHORT_MAX {
+            self.write_chunk(op, line);
+            self.write_chunk(idx as u8, line);
+        } else {
+            self.write_chunk(op.long_form().expect("instruction has no long form"), line);
+            for byte in &(idx as u32).to_be_bytes()[1..] {
+                self.write_chunk(*byte, line);
+            }
+        }
     }
     pub fn add_constant(&mut self, value: Value) -> usize {
         self.constants.push(value);
         self.constants.len() - 1
     }
+    /// 偏移 `offset` 处的字节所在的源码行
+    pub fn line_at(&self, offset: usize) -> usize {
+        self.lines.line_at(offset)
+    }
+    /// 读取 `offset` 处按大端序存放的 16 位操作数
+    pub fn read_short(&self, offset: usize) -> usize {
+        (self.code[offset] as usize) << 8 | self.code[offset + 1] as usize
+    }
+    /// 读取 `offset` 处按大端序存放的 24 位操作数
+    pub fn read_long(&self, offset: usize) -> usize {
+        (self.code[offset] as usize) << 16 | self.read_short(offset + 1)
+    }
 }
@@ -1,31 +1,182 @@
+/// 指令编码: 每条指令占一个字节, 操作数紧随其后按字节内联存放
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum OpCode {
-    OPCONSTANT,
-    OPNIL,
-    OPTRUE,
-    OPFALSE,
-    OPRETURN,
-    OPEQUAL,
-    OPGREATER,
-    OPLESS,
-    OPADD,
-    OPSUBTRACT,
-    OPMULTIPLY,
-    OPDIVIDE,
-    OPNOT,
-    OPNEGATE,
-    OPVALUEIDX(usize),
+    OPCONSTANT = 0,
+    OPCONSTANT_LONG = 1,
+    OPNIL = 2,
+    OPTRUE = 3,
+    OPFALSE = 4,
+    OPRETURN = 5,
+    OPEQUAL = 6,
+    OPGREATER = 7,
+    OPLESS = 8,
+    OPADD = 9,
+    OPSUBTRACT = 10,
+    OPMULTIPLY = 11,
+    OPDIVIDE = 12,
+    OPNOT = 13,
+    OPNEGATE = 14,
+    OPPRINT = 15,
+    OPPOP = 16,
+    OPDEFINE_GLOBAL = 17,
+    OPGET_GLOBAL = 18,
+    OPSET_GLOBAL = 19,
+    OPGET_LOCAL = 20,
+    OPSET_LOCAL = 21,
+    OPJUMP = 22,
+    OPJUMP_IF_FALSE = 23,
+    OPLOOP = 24,
+    OPCALL = 25,
+    OPCLOSURE = 26,
+    OPGET_UPVALUE = 27,
+    OPSET_UPVALUE = 28,
+    OPCLOSE_UPVALUE = 29,
+    OPCLASS = 30,
+    OPGET_PROPERTY = 31,
+    OPSET_PROPERTY = 32,
+    OPMETHOD = 33,
+    OPINVOKE = 34,
+    OPINHERIT = 35,
+    OPGET_SUPER = 36,
+    OPSUPER_INVOKE = 37,
+    // 以下指令与去掉 `_LONG` 后缀的指令相同, 只是常量下标占三个字节
+    OPDEFINE_GLOBAL_LONG = 38,
+    OPGET_GLOBAL_LONG = 39,
+    OPSET_GLOBAL_LONG = 40,
+    OPCLOSURE_LONG = 41,
+    OPCLASS_LONG = 42,
+    OPGET_PROPERTY_LONG = 43,
+    OPSET_PROPERTY_LONG = 44,
+    OPMETHOD_LONG = 45,
+    OPINVOKE_LONG = 46,
+    OPGET_SUPER_LONG = 47,
+    OPSUPER_INVOKE_LONG = 48,
 }
+
 impl OpCode {
-    pub fn to_byte(&self) -> u8 {
-        let disc = std::mem::discriminant(self);
-        let value = unsafe { *(&disc as *const _ as *const u8) }; // 可能存在风险
-        return value as u8;
+    /// 带常量下标的指令对应的三字节下标版本, 其他指令返回 `None`
+    pub fn long_form(self) -> Option<OpCode> {
+        use OpCode::*;
+        let op = match self {
+            OPCONSTANT => OPCONSTANT_LONG,
+            OPDEFINE_GLOBAL => OPDEFINE_GLOBAL_LONG,
+            OPGET_GLOBAL => OPGET_GLOBAL_LONG,
+            OPSET_GLOBAL => OPSET_GLOBAL_LONG,
+            OPCLOSURE => OPCLOSURE_LONG,
+            OPCLASS => OPCLASS_LONG,
+            OPGET_PROPERTY => OPGET_PROPERTY_LONG,
+            OPSET_PROPERTY => OPSET_PROPERTY_LONG,
+            OPMETHOD => OPMETHOD_LONG,
+            OPINVOKE => OPINVOKE_LONG,
+            OPGET_SUPER => OPGET_SUPER_LONG,
+            OPSUPER_INVOKE => OPSUPER_INVOKE_LONG,
+            _ => return None,
+        };
+        Some(op)
     }
-    pub fn as_value_idx(&self) -> usize {
+    /// `_LONG` 指令对应的单字节下标版本, 其他指令原样返回
+    pub fn short_form(self) -> OpCode {
+        use OpCode::*;
         match self {
-            OpCode::OPVALUEIDX(idx) => idx.clone(),
-            _ => panic!("not a value idx"),
+            OPCONSTANT_LONG => OPCONSTANT,
+            OPDEFINE_GLOBAL_LONG => OPDEFINE_GLOBAL,
+            OPGET_GLOBAL_LONG => OPGET_GLOBAL,
+            OPSET_GLOBAL_LONG => OPSET_GLOBAL,
+            OPCLOSURE_LONG => OPCLOSURE,
+            OPCLASS_LONG => OPCLASS,
+            OPGET_PROPERTY_LONG => OPGET_PROPERTY,
+            OPSET_PROPERTY_LONG => OPSET_PROPERTY,
+            OPMETHOD_LONG => OPMETHOD,
+            OPINVOKE_LONG => OPINVOKE,
+            OPGET_SUPER_LONG => OPGET_SUPER,
+            OPSUPER_INVOKE_LONG => OPSUPER_INVOKE,
+            op => op,
         }
     }
+    pub fn is_long(self) -> bool {
+        self.short_form() != self
+    }
+}
+
+/// 无法解码的指令字节
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidOpCode(pub u8);
+
+impl From<OpCode> for u8 {
+    fn from(op: OpCode) -> Self {
+        op as u8
+    }
+}
+impl TryFrom<u8> for OpCode {
+    type Error = InvalidOpCode;
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        use OpCode::*;
+        let op = match byte {
+            0 => OPCONSTANT,
+            1 => OPCONSTANT_LONG,
+            2 => OPNIL,
+            3 => OPTRUE,
+            4 => OPFALSE,
+            5 => OPRETURN,
+            6 => OPEQUAL,
+            7 => OPGREATER,
+            8 => OPLESS,
+            9 => OPADD,
+            10 => OPSUBTRACT,
+            11 => OPMULTIPLY,
+            12 => OPDIVIDE,
+            13 => OPNOT,
+            14 => OPNEGATE,
+            15 => OPPRINT,
+            16 => OPPOP,
+            17 => OPDEFINE_GLOBAL,
+            18 => OPGET_GLOBAL,
+            19 => OPSET_GLOBAL,
+            20 => OPGET_LOCAL,
+            21 => OPSET_LOCAL,
+            22 => OPJUMP,
+            23 => OPJUMP_IF_FALSE,
+            24 => OPLOOP,
+            25 => OPCALL,
+            26 => OPCLOSURE,
+            27 => OPGET_UPVALUE,
+            28 => OPSET_UPVALUE,
+            29 => OPCLOSE_UPVALUE,
+            30 => OPCLASS,
+            31 => OPGET_PROPERTY,
+            32 => OPSET_PROPERTY,
+            33 => OPMETHOD,
+            34 => OPINVOKE,
+            35 => OPINHERIT,
+            36 => OPGET_SUPER,
+            37 => OPSUPER_INVOKE,
+            38 => OPDEFINE_GLOBAL_LONG,
+            39 => OPGET_GLOBAL_LONG,
+            40 => OPSET_GLOBAL_LONG,
+            41 => OPCLOSURE_LONG,
+            42 => OPCLASS_LONG,
+            43 => OPGET_PROPERTY_LONG,
+            44 => OPSET_PROPERTY_LONG,
+            45 => OPMETHOD_LONG,
+            46 => OPINVOKE_LONG,
+            47 => OPGET_SUPER_LONG,
+            48 => OPSUPER_INVOKE_LONG,
+            _ => return Err(InvalidOpCode(byte)),
+        };
+        Ok(op)
+    }
+}
+#[test]
+fn test() {
+    for byte in 0..=u8::MAX {
+        if let Ok(op) = OpCode::try_from(byte) {
+            assert_eq!(u8::from(op), byte);
+        }
+    }
+    assert_eq!(OpCode::try_from(OpCode::OPSUPER_INVOKE_LONG as u8 + 1), Err(InvalidOpCode(49)));
+    assert_eq!(OpCode::OPGET_GLOBAL.long_form(), Some(OpCode::OPGET_GLOBAL_LONG));
+    assert_eq!(OpCode::OPGET_GLOBAL_LONG.short_form(), OpCode::OPGET_GLOBAL);
+    assert_eq!(OpCode::OPGET_LOCAL.long_form(), None);
 }
@@ -2,5 +2,33 @@ use std::path::PathBuf;
 
 #[derive(clap::Parser)]
 pub struct CmdParser {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Script to run when no subcommand is given; starts the REPL if omitted
     pub file: Option<PathBuf>,
+    /// Print the stack and each instruction as it executes
+    #[arg(long, global = true)]
+    pub trace: bool,
+    /// Print the bytecode of each function after compiling it
+    #[arg(long, global = true)]
+    pub dump_bytecode: bool,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Compile and run a script, or run a compiled `.loxc` file
+    Run { file: PathBuf },
+    /// Compile a script and report errors without running it
+    Check { file: PathBuf },
+    /// Print the bytecode of a script or `.loxc` file without running it
+    Disasm { file: PathBuf },
+    /// Print the tokens of a script
+    Tokens { file: PathBuf },
+    /// Compile a script into a bytecode file
+    Compile {
+        file: PathBuf,
+        /// Output path, defaults to the input with a `.loxc` extension
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
@@ -0,0 +1,6 @@
+/// 正在编译的类; 嵌套的类声明会压入新的状态, 用于检查 `this` 与 `super` 的使用位置
+#[derive(Debug, Clone, Copy)]
+pub struct ClassState {
+    /// 有父类时类体处在一个声明了 `super` 局部变量的作用域中
+    pub has_superclass: bool,
+}
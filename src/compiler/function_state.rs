@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use crate::{
+    object::{ObjFunction, ObjRef},
+    value::Value,
+};
+
+use super::local::{Local, Upvalue};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FunctionKind {
+    Function,
+    /// 类中声明的方法, 槽位 0 是 `this`
+    Method,
+    /// `init` 方法, 总是返回实例本身
+    Initializer,
+    Script,
+}
+
+/// 常量去重用的键: 数字按位比较, 对象 (驻留的字符串) 按句柄比较
+#[derive(PartialEq, Eq, Hash)]
+pub enum ConstantKey {
+    Number(u64),
+    Obj(ObjRef),
+}
+impl ConstantKey {
+    pub fn new(value: Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(ConstantKey::Number(n.to_bits())),
+            Value::Obj(r) => Some(ConstantKey::Obj(r)),
+            Value::Bool(_) | Value::Nil => None,
+        }
+    }
+}
+
+/// 正在编译的一个函数; 嵌套的函数声明会压入新的状态
+pub struct FunctionState<'a> {
+    pub function: ObjFunction,
+    pub kind: FunctionKind,
+    pub locals: Vec<Local<'a>>,
+    pub upvalues: Vec<Upvalue>,
+    pub scope_depth: usize,
+    /// 已经加入常量表的值及其下标
+    pub constant_indices: HashMap<ConstantKey, usize>,
+}
+impl<'a> FunctionState<'a> {
+    pub fn new(kind: FunctionKind, name: Option<ObjRef>) -> Self {
+        // 槽位 0 留给被调用的函数本身; 方法中则是接收者, 以 `this` 访问
+        let slot_name = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Function | FunctionKind::Script => "",
+        };
+        let mut slot_zero = Local::new(slot_name);
+        slot_zero.depth = Some(0);
+        Self {
+            function: ObjFunction::new(name),
+            kind,
+            locals: vec![slot_zero],
+            upvalues: vec![],
+            scope_depth: 0,
+            constant_indices: HashMap::new(),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+/// 编译期记录的局部变量, 在 `locals` 中的下标即运行时的栈槽位
+#[derive(Debug, Clone, Copy)]
+pub struct Local<'a> {
+    pub name: &'a str,
+    /// 所在作用域深度, `None` 表示已声明但初始化表达式尚未编译完
+    pub depth: Option<usize>,
+    /// 是否被内层函数捕获; 被捕获的变量在离开作用域时要搬到堆上
+    pub is_captured: bool,
+}
+impl<'a> Local<'a> {
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            depth: None,
+            is_captured: false,
+        }
+    }
+}
+
+/// 编译期记录的捕获变量: `is_local` 为真时 `index` 是外层函数的局部变量槽位,
+/// 否则是外层函数自身的 upvalue 下标
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Upvalue {
+    pub index: usize,
+    pub is_local: bool,
+}
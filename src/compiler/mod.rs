@@ -1,49 +1,139 @@
+mod class_state;
+mod function_state;
+mod local;
 mod parse_rule;
 mod precedence;
 mod rules;
+mod statement;
 use std::collections::HashMap;
 
 use crate::{
-    chunk::{Chunk, OpCode},
-    ph,
-    parser::Parser,
+    chunk::{debug::Disassembler, Chunk, OpCode, CONSTANT_LONG_MAX},
+    diagnostic::{Diagnostic, ErrorCode},
+    object::{Obj, ObjRef},
     value::Value,
-    Scanner, Token, TokenType,
+    Scanner, Token, TokenType, VM,
 };
+use class_state::ClassState;
+use function_state::{ConstantKey, FunctionKind, FunctionState};
+use local::{Local, Upvalue};
 pub use parse_rule::*;
 use precedence::{Precedence, Precedence::*};
 use rules::*;
-use TokenType::*;
-pub struct      Compiler<'a> {
+use statement::*;
+pub struct Compiler<'a> {
     pub previous: Token<'a>, // 当前正在解析的token
     pub current: Token<'a>,  // 下一个token
-    chunk: &'a mut Chunk,
-    had_error: bool,
+    /// 编译期间分配的对象与运行时共用 VM 的堆, 分配时可能触发回收
+    vm: &'a mut VM,
+    diagnostics: Vec<Diagnostic>,
     panic_mode: bool,
+    source: &'a str,
     scanner: Scanner<'a>,
     rules: HashMap<TokenType, ParseRule>,
+    /// 嵌套函数的编译状态栈, 栈底是顶层脚本
+    states: Vec<FunctionState<'a>>,
+    /// 嵌套的类声明栈, 为空表示不在类中
+    classes: Vec<ClassState>,
+    /// REPL 模式: 顶层表达式语句打印其结果, 末尾的分号可以省略
+    repl: bool,
 }
 
+/// 单个函数内最多可容纳的局部变量个数
+const LOCALS_MAX: usize = 256;
+/// 单个函数最多可捕获的变量个数
+const UPVALUES_MAX: usize = 256;
+/// 单次调用最多可传递的参数个数
+const ARGS_MAX: usize = 255;
+/// 跳转指令能跨越的最大距离
+const JUMP_MAX: usize = u16::MAX as usize;
+
 impl<'a> Compiler<'a> {
-    pub fn new(chunk: &'a mut Chunk, source: &'a str) -> Self {
+    pub fn new(source: &'a str, vm: &'a mut VM) -> Self {
         Self {
-            chunk,
+            vm,
             rules: HashMap::new(),
-            had_error: false,
+            diagnostics: vec![],
             panic_mode: false,
-            previous: Token::new(TokenType::TokenNil, "", 0),
-            current: Token::new(TokenType::TokenNil, "", 0),
+            previous: Token::new(TokenType::TokenNil, "", 0, 0),
+            current: Token::new(TokenType::TokenNil, "", 0, 0),
+            source,
             scanner: Scanner::new(source),
+            states: vec![FunctionState::new(FunctionKind::Script, None)],
+            classes: vec![],
+            repl: false,
+        }
+    }
+    /// 以 REPL 模式编译
+    pub fn new_repl(source: &'a str, vm: &'a mut VM) -> Self {
+        Self {
+            repl: true,
+            ..Self::new(source, vm)
         }
     }
-    pub fn compile(&mut self) -> bool {
+    /// 编译整个源码, 返回顶层脚本对应的函数对象; 出错时返回收集到的全部诊断
+    pub fn compile(mut self) -> Result<ObjRef, Vec<Diagnostic>> {
         register_rules(&mut self.rules);
 
         self.advance();
-        self.expression();
-        self.consume(TokenType::TokenEof, "expect end of expression");
+        while !self.match_token(TokenType::TokenEof) {
+            declaration(&mut self);
+        }
+        let (function, _) = self.end_function();
+        if !self.diagnostics.is_empty() {
+            return Err(self.diagnostics);
+        }
+        Ok(function)
+    }
+    fn state(&self) -> &FunctionState<'a> {
+        self.states.last().expect("no function being compiled")
+    }
+    fn state_mut(&mut self) -> &mut FunctionState<'a> {
+        self.states.last_mut().expect("no function being compiled")
+    }
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state_mut().function.chunk
+    }
+    pub fn function_kind(&self) -> FunctionKind {
+        self.state().kind
+    }
+    /// 开始编译一个新函数, 函数名取自上一个 token
+    pub fn begin_function(&mut self, kind: FunctionKind) {
+        let name = self.intern(self.previous.start);
+        self.states.push(FunctionState::new(kind, Some(name)));
+    }
+    /// 结束当前函数的编译, 把它分配到堆上; 同时返回它捕获的变量, 供外层生成 `OPCLOSURE`
+    pub fn end_function(&mut self) -> (ObjRef, Vec<Upvalue>) {
         self.emit_return();
-        !self.had_error
+        // 出栈后函数的常量不再被当作根, 所以要在出栈前回收
+        self.maybe_collect();
+        let state = self.states.pop().expect("no function being compiled");
+        if self.vm.dump_bytecode && self.diagnostics.is_empty() {
+            let name = self.vm.heap.function_name(&state.function);
+            let format_value = |value| self.vm.heap.format_value(value);
+            eprint!("{}", Disassembler::new(&state.function.chunk, &format_value).chunk(&name));
+        }
+        let function = self.vm.heap.alloc(Obj::Function(state.function));
+        (function, state.upvalues)
+    }
+    /// 正在编译的函数的名字和常量尚未挂到任何堆对象上, 回收时作为额外的根
+    fn maybe_collect(&mut self) {
+        if !self.vm.heap.should_collect() {
+            return;
+        }
+        let mut roots = vec![];
+        for state in &self.states {
+            roots.extend(state.function.name.map(Value::Obj));
+            roots.extend(state.function.chunk.constants.iter().copied());
+        }
+        self.vm.collect_with_roots(&roots);
+    }
+    pub fn intern(&mut self, chars: &str) -> ObjRef {
+        self.maybe_collect();
+        self.vm.heap.intern(chars)
+    }
+    pub fn set_arity(&mut self, arity: usize) {
+        self.state_mut().function.arity = arity;
     }
     pub fn expression(&mut self) {
         parse_precedence(self, PrecAssignment);
@@ -64,9 +154,19 @@ impl<'a> Compiler<'a> {
             if self.current.t_type != TokenType::TokenError {
                 break;
             }
-            self.error_at_current(self.current.start);
+            self.error_at(self.current, ErrorCode::Lexical, self.current.start);
         }
     }
+    pub fn check(&self, t_type: TokenType) -> bool {
+        self.current.is(t_type)
+    }
+    pub fn match_token(&mut self, t_type: TokenType) -> bool {
+        if !self.check(t_type) {
+            return false;
+        }
+        self.advance();
+        true
+    }
     pub fn consume(&mut self, t_type: TokenType, message: &str) {
         if self.current.t_type == t_type {
             self.advance();
@@ -74,34 +174,251 @@ impl<'a> Compiler<'a> {
         }
         self.error_at_current(message);
     }
-    fn error_at(&mut self, token: Token, message: &str) {
+    /// 出错后跳过 token 直到语句边界, 退出 panic 模式以便继续报告后续的独立错误
+    pub fn synchronize(&mut self) {
+        self.panic_mode = false;
+        while !self.check(TokenType::TokenEof) {
+            if self.previous.is(TokenType::TokenSemicolon) {
+                return;
+            }
+            match self.current.t_type {
+                TokenType::TokenClass
+                | TokenType::TokenFun
+                | TokenType::TokenVar
+                | TokenType::TokenFor
+                | TokenType::TokenIf
+                | TokenType::TokenWhile
+                | TokenType::TokenPrint
+                | TokenType::TokenReturn => return,
+                _ => self.advance(),
+            }
+        }
+    }
+    fn error_at(&mut self, token: Token, code: ErrorCode, message: &str) {
         if self.panic_mode {
             return;
         }
         self.panic_mode = true;
-        eprint!("[line {}] Error", token.line);
-        if token.t_type == TokenType::TokenEof {
-            eprintln!(" at end");
-        } else if token.t_type == TokenType::TokenError {
-            // Nothing.
-        } else {
-            eprint!(" at '{}'", token.start);
-        }
-        eprint!(": {}\n", message);
-        self.had_error = true;
+        let span = token.offset..token.offset + token.len;
+        let diagnostic = Diagnostic::error(code, message, self.source, span);
+        self.diagnostics.push(diagnostic);
     }
     fn error_at_current(&mut self, message: &str) {
-        self.error_at(self.current, message);
+        self.error_at(self.current, ErrorCode::Syntax, message);
+    }
+    fn error_at_current_with_code(&mut self, code: ErrorCode, message: &str) {
+        self.error_at(self.current, code, message);
     }
     fn error(&mut self, message: &str) {
-        self.error_at(self.previous, message);
+        self.error_with_code(ErrorCode::Syntax, message);
+    }
+    fn error_with_code(&mut self, code: ErrorCode, message: &str) {
+        self.error_at(self.previous, code, message);
+    }
+
+    pub fn identifier_constant(&mut self, name: &str) -> usize {
+        let obj = self.intern(name);
+        self.make_constant(Value::Obj(obj))
+    }
+    pub fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+    pub fn end_scope(&mut self) {
+        self.state_mut().scope_depth -= 1;
+        let scope_depth = self.state().scope_depth;
+        while let Some(local) = self.state().locals.last() {
+            if local.depth.is_some_and(|d| d <= scope_depth) {
+                break;
+            }
+            if local.is_captured {
+                self.emit_byte(OpCode::OPCLOSE_UPVALUE);
+            } else {
+                self.emit_byte(OpCode::OPPOP);
+            }
+            self.state_mut().locals.pop();
+        }
+    }
+    pub fn add_local(&mut self, name: &'a str) {
+        if self.state().locals.len() == LOCALS_MAX {
+            self.error_with_code(ErrorCode::TooManyLocals, "too many local variables in function");
+            return;
+        }
+        self.state_mut().locals.push(Local::new(name));
+    }
+    fn declare_variable(&mut self) {
+        let state = self.state();
+        if state.scope_depth == 0 {
+            return;
+        }
+        let name = self.previous.start;
+        let duplicated = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth.is_none_or(|d| d >= state.scope_depth))
+            .any(|local| local.name == name);
+        if duplicated {
+            self.error_with_code(
+                ErrorCode::Redeclaration,
+                "already a variable with this name in this scope",
+            );
+        }
+        self.add_local(name);
+    }
+    /// 查找局部变量对应的栈槽位, 找不到时再尝试作为 upvalue 或全局变量
+    pub fn resolve_local(&mut self, name: &str) -> Option<usize> {
+        self.resolve_local_in(self.states.len() - 1, name)
+    }
+    fn resolve_local_in(&mut self, state_idx: usize, name: &str) -> Option<usize> {
+        let (slot, local) = self.states[state_idx]
+            .locals
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, local)| local.name == name)?;
+        if local.depth.is_none() {
+            self.error_with_code(
+                ErrorCode::UninitializedRead,
+                "can't read local variable in its own initializer",
+            );
+        }
+        Some(slot)
+    }
+    /// 在外层函数中查找变量, 沿途每一层函数都登记一个 upvalue
+    pub fn resolve_upvalue(&mut self, name: &str) -> Option<usize> {
+        self.resolve_upvalue_in(self.states.len() - 1, name)
+    }
+    fn resolve_upvalue_in(&mut self, state_idx: usize, name: &str) -> Option<usize> {
+        if state_idx == 0 {
+            return None;
+        }
+        if let Some(local) = self.resolve_local_in(state_idx - 1, name) {
+            self.states[state_idx - 1].locals[local].is_captured = true;
+            return Some(self.add_upvalue(state_idx, local, true));
+        }
+        let upvalue = self.resolve_upvalue_in(state_idx - 1, name)?;
+        Some(self.add_upvalue(state_idx, upvalue, false))
+    }
+    fn add_upvalue(&mut self, state_idx: usize, index: usize, is_local: bool) -> usize {
+        let upvalue = Upvalue { index, is_local };
+        let state = &self.states[state_idx];
+        if let Some(existing) = state.upvalues.iter().position(|u| *u == upvalue) {
+            return existing;
+        }
+        if state.upvalues.len() == UPVALUES_MAX {
+            self.error_with_code(
+                ErrorCode::TooManyUpvalues,
+                "too many closure variables in function",
+            );
+            return 0;
+        }
+        let state = &mut self.states[state_idx];
+        state.upvalues.push(upvalue);
+        state.function.upvalue_count = state.upvalues.len();
+        state.upvalues.len() - 1
+    }
+    pub fn parse_variable(&mut self, message: &str) -> usize {
+        self.consume(TokenType::TokenIdentifier, message);
+        self.declare_variable();
+        if self.state().scope_depth > 0 {
+            return 0;
+        }
+        self.identifier_constant(self.previous.start)
+    }
+    pub fn mark_initialized(&mut self) {
+        let state = self.state_mut();
+        if state.scope_depth == 0 {
+            return;
+        }
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(state.scope_depth);
+        }
+    }
+    pub fn define_variable(&mut self, global: usize) {
+        if self.state().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+        self.emit_with_constant(OpCode::OPDEFINE_GLOBAL, global);
     }
 
-    fn emit_byte(&mut self, byte: OpCode) {
-        self.chunk.write_chunk(byte, self.previous.line);
+    fn emit_byte(&mut self, byte: impl Into<u8>) {
+        let line = self.previous.line;
+        self.chunk().write_chunk(byte, line);
+    }
+    /// 写入单字节操作数; 各类上限保证它不会超过 255, 出错时写入的内容不会被执行
+    pub fn emit_operand(&mut self, operand: usize) {
+        self.emit_byte(operand as u8);
+    }
+    /// 按大端序写入 16 位操作数
+    pub fn emit_short(&mut self, operand: usize) {
+        self.emit_byte((operand >> 8) as u8);
+        self.emit_byte(operand as u8);
+    }
+    pub fn emit_with_operand(&mut self, op: OpCode, operand: usize) {
+        self.emit_byte(op);
+        self.emit_operand(operand);
+    }
+    /// 添加一个常量并返回下标; 相同的数字和字符串复用同一个常量
+    pub fn make_constant(&mut self, value: Value) -> usize {
+        let key = ConstantKey::new(value);
+        if let Some(idx) = key.as_ref().and_then(|key| self.state().constant_indices.get(key)) {
+            return *idx;
+        }
+        if self.chunk().constants.len() > CONSTANT_LONG_MAX {
+            self.error_with_code(ErrorCode::TooManyConstants, "too many constants in one chunk");
+            return 0;
+        }
+        let idx = self.chunk().add_constant(value);
+        if let Some(key) = key {
+            self.state_mut().constant_indices.insert(key, idx);
+        }
+        idx
     }
-    fn emit_return(&mut self) {
-        self.emit_byte(OpCode::OPRETURN);
+    /// 写入以常量下标为操作数的指令, 下标超过 255 时改用它的 `_LONG` 版本
+    pub fn emit_with_constant(&mut self, op: OpCode, idx: usize) {
+        let line = self.previous.line;
+        self.chunk().write_indexed(op, idx, line);
+    }
+    fn emit_constant(&mut self, value: Value) {
+        let idx = self.make_constant(value);
+        self.emit_with_constant(OpCode::OPCONSTANT, idx);
+    }
+    /// 写入一条占位的跳转指令, 返回操作数所在位置, 供 `patch_jump` 回填
+    pub fn emit_jump(&mut self, instruction: OpCode) -> usize {
+        self.emit_byte(instruction);
+        self.emit_short(JUMP_MAX);
+        self.code_len() - 2
+    }
+    pub fn patch_jump(&mut self, offset: usize) {
+        // 跳过操作数本身
+        let jump = self.code_len() - offset - 2;
+        if jump > JUMP_MAX {
+            self.error_with_code(ErrorCode::JumpTooLarge, "too much code to jump over");
+        }
+        let code = &mut self.chunk().code;
+        code[offset] = (jump >> 8) as u8;
+        code[offset + 1] = jump as u8;
+    }
+    pub fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_byte(OpCode::OPLOOP);
+        let offset = self.code_len() - loop_start + 2;
+        if offset > JUMP_MAX {
+            self.error_with_code(ErrorCode::JumpTooLarge, "loop body too large");
+        }
+        self.emit_short(offset);
+    }
+    pub fn code_len(&self) -> usize {
+        self.state().function.chunk.code.len()
+    }
+    /// 函数体执行到末尾时隐式返回 nil
+    pub fn emit_return(&mut self) {
+        if self.function_kind() == FunctionKind::Initializer {
+            self.emit_with_operand(OpCode::OPGET_LOCAL, 0);
+            self.emit_byte(OpCode::OPRETURN);
+        } else {
+            self.emit_bytes(&[OpCode::OPNIL, OpCode::OPRETURN]);
+        }
     }
     fn emit_bytes(&mut self, bytes: &[OpCode]) {
         for byte in bytes {
@@ -111,8 +428,8 @@ impl<'a> Compiler<'a> {
 }
 #[test]
 fn test() {
-    let mut chunk = Chunk::new();
-    let mut compiler = Compiler::new(&mut chunk, "1+2*3");
-    compiler.compile();
-    chunk.disassemble("?");
+    let mut vm = VM::new();
+    let compiler = Compiler::new("fun f(a) { return a + 2 * 3; } print f(1);", &mut vm);
+    let function = compiler.compile().unwrap();
+    vm.heap.as_function(function).unwrap().chunk.disassemble("?");
 }
@@ -3,9 +3,10 @@ use super::{Compiler, Precedence};
 #[macro_export]
 macro_rules! ph {
     () => {
-        Option<fn(& mut Compiler)>
+        Option<fn(&mut Compiler, bool)>
     };
 }
+/// 前缀/中缀解析函数的第二个参数为 `can_assign`, 表示当前位置能否出现赋值
 pub struct ParseRule {
     pub prefix: ph!(),
     pub infix: ph!(),
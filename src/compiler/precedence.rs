@@ -1,4 +1,5 @@
 use Precedence::*;
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum Precedence {
     PrecNone,
@@ -1,30 +1,69 @@
 use std::collections::HashMap;
 
-use crate::{chunk::OpCode, ph};
+use crate::{chunk::OpCode, diagnostic::ErrorCode, ph, value::Value};
 
-use super::{parse_rule, precedence, Compiler, ParseRule, Precedence, TokenType};
+use super::{Compiler, ParseRule, Precedence, TokenType, ARGS_MAX};
 type RuleMap = HashMap<TokenType, ParseRule>;
 use Precedence::*;
 use TokenType::*;
-pub fn literal(c: &mut Compiler) {
-    let value = c.previous.as_value();
-    c.chunk.write_constant(value, c.previous.line);
+pub fn literal(c: &mut Compiler, _can_assign: bool) {
+    match c.previous.t_type {
+        TokenNil => c.emit_byte(OpCode::OPNIL),
+        TokenTrue => c.emit_byte(OpCode::OPTRUE),
+        TokenFalse => c.emit_byte(OpCode::OPFALSE),
+        _ => {
+            let value = c.previous.as_value();
+            c.emit_constant(value);
+        }
+    }
+}
+pub fn string(c: &mut Compiler, _can_assign: bool) {
+    // 去掉首尾的引号
+    let lexeme = c.previous.start;
+    let chars = &lexeme[1..lexeme.len() - 1];
+    let obj = c.intern(chars);
+    c.emit_constant(Value::Obj(obj));
+}
+pub fn variable(c: &mut Compiler, can_assign: bool) {
+    named_variable(c, c.previous.start, can_assign);
+}
+pub fn named_variable(c: &mut Compiler, name: &str, can_assign: bool) {
+    let (get_op, set_op, arg) = if let Some(slot) = c.resolve_local(name) {
+        (OpCode::OPGET_LOCAL, OpCode::OPSET_LOCAL, slot)
+    } else if let Some(idx) = c.resolve_upvalue(name) {
+        (OpCode::OPGET_UPVALUE, OpCode::OPSET_UPVALUE, idx)
+    } else {
+        let arg = c.identifier_constant(name);
+        (OpCode::OPGET_GLOBAL, OpCode::OPSET_GLOBAL, arg)
+    };
+    let op = if can_assign && c.match_token(TokenType::TokenEqual) {
+        c.expression();
+        set_op
+    } else {
+        get_op
+    };
+    // 全局变量的操作数是名字常量的下标, 可能需要三字节的 `_LONG` 版本
+    if op.long_form().is_some() {
+        c.emit_with_constant(op, arg);
+    } else {
+        c.emit_with_operand(op, arg);
+    }
 }
-pub fn grouping(c: &mut Compiler) {
-    parse_precedence(c, Precedence::PrecCall);
+pub fn grouping(c: &mut Compiler, _can_assign: bool) {
+    c.expression();
     c.consume(TokenType::TokenRightParen, "expect ')' after expression");
 }
-pub fn unary(c: &mut Compiler) {
+pub fn unary(c: &mut Compiler, _can_assign: bool) {
     let token_type = c.previous.t_type;
     parse_precedence(c, Precedence::PrecUnary);
 
     match token_type {
         TokenType::TokenMinus => c.emit_byte(OpCode::OPNEGATE),
         TokenType::TokenBang => c.emit_byte(OpCode::OPNOT),
-        _ => return,
+        _ => (),
     }
 }
-pub fn binary(c: &mut Compiler) {
+pub fn binary(c: &mut Compiler, _can_assign: bool) {
     let operator_type = c.previous.t_type;
     parse_precedence(c, c.previous_rule().prec.next());
     match operator_type {
@@ -32,8 +71,103 @@ pub fn binary(c: &mut Compiler) {
         TokenType::TokenMinus => c.emit_byte(OpCode::OPSUBTRACT),
         TokenType::TokenStar => c.emit_byte(OpCode::OPMULTIPLY),
         TokenType::TokenSlash => c.emit_byte(OpCode::OPDIVIDE),
-        _ => return,
+        TokenType::TokenEqualEqual => c.emit_byte(OpCode::OPEQUAL),
+        TokenType::TokenBangEqual => c.emit_bytes(&[OpCode::OPEQUAL, OpCode::OPNOT]),
+        TokenType::TokenGreater => c.emit_byte(OpCode::OPGREATER),
+        TokenType::TokenGreaterEqual => c.emit_bytes(&[OpCode::OPLESS, OpCode::OPNOT]),
+        TokenType::TokenLess => c.emit_byte(OpCode::OPLESS),
+        TokenType::TokenLessEqual => c.emit_bytes(&[OpCode::OPGREATER, OpCode::OPNOT]),
+        _ => (),
+    }
+}
+pub fn call(c: &mut Compiler, _can_assign: bool) {
+    let arg_count = argument_list(c);
+    c.emit_with_operand(OpCode::OPCALL, arg_count);
+}
+fn argument_list(c: &mut Compiler) -> usize {
+    let mut arg_count = 0;
+    if !c.check(TokenType::TokenRightParen) {
+        loop {
+            c.expression();
+            if arg_count == ARGS_MAX {
+                c.error_with_code(ErrorCode::TooManyArguments, "can't have more than 255 arguments");
+            }
+            arg_count += 1;
+            if !c.match_token(TokenType::TokenComma) {
+                break;
+            }
+        }
     }
+    c.consume(TokenType::TokenRightParen, "expect ')' after arguments");
+    arg_count
+}
+/// `obj.name`: 属性读取, 在可赋值的位置上也可以是属性写入
+pub fn dot(c: &mut Compiler, can_assign: bool) {
+    c.consume(TokenType::TokenIdentifier, "expect property name after '.'");
+    let name = c.identifier_constant(c.previous.start);
+    if can_assign && c.match_token(TokenType::TokenEqual) {
+        c.expression();
+        c.emit_with_constant(OpCode::OPSET_PROPERTY, name);
+    } else if c.match_token(TokenType::TokenLeftParen) {
+        // 直接调用方法, 省去创建绑定方法
+        let arg_count = argument_list(c);
+        c.emit_with_constant(OpCode::OPINVOKE, name);
+        c.emit_operand(arg_count);
+    } else {
+        c.emit_with_constant(OpCode::OPGET_PROPERTY, name);
+    }
+}
+pub fn this(c: &mut Compiler, _can_assign: bool) {
+    if c.classes.is_empty() {
+        c.error_with_code(ErrorCode::ThisOutsideClass, "can't use 'this' outside of a class");
+        return;
+    }
+    // `this` 是方法的槽位 0, 按普通变量解析即可被内层函数捕获
+    variable(c, false);
+}
+/// `super.name`: 在父类中查找方法并绑定到 `this`
+pub fn super_(c: &mut Compiler, _can_assign: bool) {
+    match c.classes.last() {
+        None => c.error_with_code(
+            ErrorCode::SuperOutsideSubclass,
+            "can't use 'super' outside of a class",
+        ),
+        Some(class) if !class.has_superclass => c.error_with_code(
+            ErrorCode::SuperOutsideSubclass,
+            "can't use 'super' in a class with no superclass",
+        ),
+        Some(_) => {}
+    }
+    c.consume(TokenType::TokenDot, "expect '.' after 'super'");
+    c.consume(TokenType::TokenIdentifier, "expect superclass method name");
+    let name = c.identifier_constant(c.previous.start);
+
+    named_variable(c, "this", false);
+    if c.match_token(TokenType::TokenLeftParen) {
+        let arg_count = argument_list(c);
+        named_variable(c, "super", false);
+        c.emit_with_constant(OpCode::OPSUPER_INVOKE, name);
+        c.emit_operand(arg_count);
+    } else {
+        named_variable(c, "super", false);
+        c.emit_with_constant(OpCode::OPGET_SUPER, name);
+    }
+}
+/// `and`: 左操作数为假时短路, 保留左操作数作为结果
+pub fn and(c: &mut Compiler, _can_assign: bool) {
+    let end_jump = c.emit_jump(OpCode::OPJUMP_IF_FALSE);
+    c.emit_byte(OpCode::OPPOP);
+    parse_precedence(c, PrecAnd);
+    c.patch_jump(end_jump);
+}
+/// `or`: 左操作数为真时短路, 保留左操作数作为结果
+pub fn or(c: &mut Compiler, _can_assign: bool) {
+    let else_jump = c.emit_jump(OpCode::OPJUMP_IF_FALSE);
+    let end_jump = c.emit_jump(OpCode::OPJUMP);
+    c.patch_jump(else_jump);
+    c.emit_byte(OpCode::OPPOP);
+    parse_precedence(c, PrecOr);
+    c.patch_jump(end_jump);
 }
 pub fn parse_precedence(c: &mut Compiler, precedence: Precedence) {
     c.advance();
@@ -43,7 +177,9 @@ pub fn parse_precedence(c: &mut Compiler, precedence: Precedence) {
         c.error(&message);
         return;
     };
-    prefix_rule(c);
+    // 只有在最低的赋值优先级下, 才允许把 `=` 当作赋值处理
+    let can_assign = precedence <= PrecAssignment;
+    prefix_rule(c, can_assign);
     loop {
         if precedence > c.current_rule().prec {
             break;
@@ -54,7 +190,10 @@ pub fn parse_precedence(c: &mut Compiler, precedence: Precedence) {
             c.error(&message);
             return;
         };
-        infix_rule(c);
+        infix_rule(c, can_assign);
+    }
+    if can_assign && c.match_token(TokenType::TokenEqual) {
+        c.error_with_code(ErrorCode::InvalidAssignmentTarget, "invalid assignment target");
     }
 }
 fn r_r(r: &mut RuleMap, t: TokenType, prefix: ph!(), infix: ph!(), prec: Precedence) {
@@ -68,12 +207,12 @@ fn r_r(r: &mut RuleMap, t: TokenType, prefix: ph!(), infix: ph!(), prec: Precede
     );
 }
 pub fn register_rules(r: &mut RuleMap) {
-    r_r(r, TokenLeftParen, Some(grouping), None, PrecNone);
+    r_r(r, TokenLeftParen, Some(grouping), Some(call), PrecCall);
     r_r(r, TokenRightParen, None, None, PrecNone);
     r_r(r, TokenLeftBrace, None, None, PrecNone);
     r_r(r, TokenRightBrace, None, None, PrecNone);
     r_r(r, TokenComma, None, None, PrecNone);
-    r_r(r, TokenDot, None, None, PrecNone);
+    r_r(r, TokenDot, None, Some(dot), PrecCall);
     r_r(r, TokenMinus, Some(unary), Some(binary), PrecTerm);
     r_r(r, TokenPlus, None, Some(binary), PrecTerm);
     r_r(r, TokenSemicolon, None, None, PrecNone);
@@ -87,10 +226,10 @@ pub fn register_rules(r: &mut RuleMap) {
     r_r(r, TokenGreaterEqual, None, Some(binary), PrecComparison);
     r_r(r, TokenLess, None, Some(binary), PrecComparison);
     r_r(r, TokenLessEqual, None, Some(binary), PrecComparison);
-    r_r(r, TokenIdentifier, None, None, PrecNone);
-    r_r(r, TokenString, None, None, PrecNone);
+    r_r(r, TokenIdentifier, Some(variable), None, PrecNone);
+    r_r(r, TokenString, Some(string), None, PrecNone);
     r_r(r, TokenNumber, Some(literal), None, PrecNone);
-    r_r(r, TokenAnd, None, None, PrecNone);
+    r_r(r, TokenAnd, None, Some(and), PrecAnd);
     r_r(r, TokenClass, None, None, PrecNone);
     r_r(r, TokenElse, None, None, PrecNone);
     r_r(r, TokenFalse, Some(literal), None, PrecNone);
@@ -98,11 +237,11 @@ pub fn register_rules(r: &mut RuleMap) {
     r_r(r, TokenFun, None, None, PrecNone);
     r_r(r, TokenIf, None, None, PrecNone);
     r_r(r, TokenNil, Some(literal), None, PrecNone);
-    r_r(r, TokenOr, None, None, PrecNone);
+    r_r(r, TokenOr, None, Some(or), PrecOr);
     r_r(r, TokenPrint, None, None, PrecNone);
     r_r(r, TokenReturn, None, None, PrecNone);
-    r_r(r, TokenSuper, None, None, PrecNone);
-    r_r(r, TokenThis, None, None, PrecNone);
+    r_r(r, TokenSuper, Some(super_), None, PrecNone);
+    r_r(r, TokenThis, Some(this), None, PrecNone);
     r_r(r, TokenTrue, Some(literal), None, PrecNone);
     r_r(r, TokenVar, None, None, PrecNone);
     r_r(r, TokenWhile, None, None, PrecNone);
@@ -0,0 +1,257 @@
+use crate::{chunk::OpCode, diagnostic::ErrorCode, value::Value};
+
+use super::{named_variable, variable, ClassState, Compiler, FunctionKind, TokenType, ARGS_MAX};
+
+pub fn declaration(c: &mut Compiler) {
+    if c.match_token(TokenType::TokenClass) {
+        class_declaration(c);
+    } else if c.match_token(TokenType::TokenFun) {
+        fun_declaration(c);
+    } else if c.match_token(TokenType::TokenVar) {
+        var_declaration(c);
+    } else {
+        statement(c);
+    }
+    if c.panic_mode {
+        c.synchronize();
+    }
+}
+fn class_declaration(c: &mut Compiler) {
+    c.consume(TokenType::TokenIdentifier, "expect class name");
+    let class_name = c.previous.start;
+    let name_constant = c.identifier_constant(class_name);
+    c.declare_variable();
+
+    c.emit_with_constant(OpCode::OPCLASS, name_constant);
+    c.define_variable(name_constant);
+    c.classes.push(ClassState { has_superclass: false });
+
+    if c.match_token(TokenType::TokenLess) {
+        c.consume(TokenType::TokenIdentifier, "expect superclass name");
+        variable(c, false);
+        if c.previous.start == class_name {
+            c.error_with_code(ErrorCode::SelfInheritance, "a class can't inherit from itself");
+        }
+        // 父类保存在类体外层作用域的 `super` 变量中, 方法通过 upvalue 捕获它
+        c.begin_scope();
+        c.add_local("super");
+        c.define_variable(0);
+
+        named_variable(c, class_name, false);
+        c.emit_byte(OpCode::OPINHERIT);
+        c.classes.last_mut().expect("no class being compiled").has_superclass = true;
+    }
+
+    // 把类重新压栈, 供 OPMETHOD 把方法挂到它上面
+    named_variable(c, class_name, false);
+    c.consume(TokenType::TokenLeftBrace, "expect '{' before class body");
+    while !c.check(TokenType::TokenRightBrace) && !c.check(TokenType::TokenEof) {
+        method(c);
+    }
+    c.consume(TokenType::TokenRightBrace, "expect '}' after class body");
+    c.emit_byte(OpCode::OPPOP);
+
+    if c.classes.last().is_some_and(|class| class.has_superclass) {
+        c.end_scope();
+    }
+    c.classes.pop();
+}
+fn method(c: &mut Compiler) {
+    c.consume(TokenType::TokenIdentifier, "expect method name");
+    let constant = c.identifier_constant(c.previous.start);
+    let kind = if c.previous.start == "init" {
+        FunctionKind::Initializer
+    } else {
+        FunctionKind::Method
+    };
+    function(c, kind);
+    c.emit_with_constant(OpCode::OPMETHOD, constant);
+}
+fn fun_declaration(c: &mut Compiler) {
+    let global = c.parse_variable("expect function name");
+    // 函数体内可以递归引用自身, 因此在编译函数体之前就标记为已初始化
+    c.mark_initialized();
+    function(c, FunctionKind::Function);
+    c.define_variable(global);
+}
+fn function(c: &mut Compiler, kind: FunctionKind) {
+    c.begin_function(kind);
+    c.begin_scope();
+
+    c.consume(TokenType::TokenLeftParen, "expect '(' after function name");
+    let mut arity = 0;
+    if !c.check(TokenType::TokenRightParen) {
+        loop {
+            arity += 1;
+            if arity > ARGS_MAX {
+                c.error_at_current_with_code(
+                    ErrorCode::TooManyArguments,
+                    "can't have more than 255 parameters",
+                );
+            }
+            let constant = c.parse_variable("expect parameter name");
+            c.define_variable(constant);
+            if !c.match_token(TokenType::TokenComma) {
+                break;
+            }
+        }
+    }
+    c.set_arity(arity);
+    c.consume(TokenType::TokenRightParen, "expect ')' after parameters");
+    c.consume(TokenType::TokenLeftBrace, "expect '{' before function body");
+    block(c);
+
+    // 函数返回时整个调用帧被丢弃, 不需要 end_scope 逐个弹出局部变量
+    let (function, upvalues) = c.end_function();
+    let constant = c.make_constant(Value::Obj(function));
+    c.emit_with_constant(OpCode::OPCLOSURE, constant);
+    // 捕获变量最多 256 个, 个数用两个字节存放
+    c.emit_short(upvalues.len());
+    for upvalue in upvalues {
+        c.emit_operand(upvalue.is_local as usize);
+        c.emit_operand(upvalue.index);
+    }
+}
+fn var_declaration(c: &mut Compiler) {
+    let global = c.parse_variable("expect variable name");
+    if c.match_token(TokenType::TokenEqual) {
+        c.expression();
+    } else {
+        c.emit_byte(OpCode::OPNIL);
+    }
+    c.consume(TokenType::TokenSemicolon, "expect ';' after variable declaration");
+    c.define_variable(global);
+}
+pub fn statement(c: &mut Compiler) {
+    if c.match_token(TokenType::TokenPrint) {
+        print_statement(c);
+    } else if c.match_token(TokenType::TokenReturn) {
+        return_statement(c);
+    } else if c.match_token(TokenType::TokenIf) {
+        if_statement(c);
+    } else if c.match_token(TokenType::TokenWhile) {
+        while_statement(c);
+    } else if c.match_token(TokenType::TokenFor) {
+        for_statement(c);
+    } else if c.match_token(TokenType::TokenLeftBrace) {
+        c.begin_scope();
+        block(c);
+        c.end_scope();
+    } else {
+        expression_statement(c);
+    }
+}
+fn block(c: &mut Compiler) {
+    while !c.check(TokenType::TokenRightBrace) && !c.check(TokenType::TokenEof) {
+        declaration(c);
+    }
+    c.consume(TokenType::TokenRightBrace, "expect '}' after block");
+}
+fn if_statement(c: &mut Compiler) {
+    c.consume(TokenType::TokenLeftParen, "expect '(' after 'if'");
+    c.expression();
+    c.consume(TokenType::TokenRightParen, "expect ')' after condition");
+
+    let then_jump = c.emit_jump(OpCode::OPJUMP_IF_FALSE);
+    c.emit_byte(OpCode::OPPOP);
+    statement(c);
+    let else_jump = c.emit_jump(OpCode::OPJUMP);
+
+    c.patch_jump(then_jump);
+    c.emit_byte(OpCode::OPPOP);
+    if c.match_token(TokenType::TokenElse) {
+        statement(c);
+    }
+    c.patch_jump(else_jump);
+}
+fn while_statement(c: &mut Compiler) {
+    let loop_start = c.code_len();
+    c.consume(TokenType::TokenLeftParen, "expect '(' after 'while'");
+    c.expression();
+    c.consume(TokenType::TokenRightParen, "expect ')' after condition");
+
+    let exit_jump = c.emit_jump(OpCode::OPJUMP_IF_FALSE);
+    c.emit_byte(OpCode::OPPOP);
+    statement(c);
+    c.emit_loop(loop_start);
+
+    c.patch_jump(exit_jump);
+    c.emit_byte(OpCode::OPPOP);
+}
+/// `for` 脱糖为 while 循环: 初始化语句放在新作用域里, 递增子句在循环体之后执行
+fn for_statement(c: &mut Compiler) {
+    c.begin_scope();
+    c.consume(TokenType::TokenLeftParen, "expect '(' after 'for'");
+    if c.match_token(TokenType::TokenSemicolon) {
+        // 没有初始化语句
+    } else if c.match_token(TokenType::TokenVar) {
+        var_declaration(c);
+    } else {
+        expression_statement(c);
+    }
+
+    let mut loop_start = c.code_len();
+    let mut exit_jump = None;
+    if !c.match_token(TokenType::TokenSemicolon) {
+        c.expression();
+        c.consume(TokenType::TokenSemicolon, "expect ';' after loop condition");
+        exit_jump = Some(c.emit_jump(OpCode::OPJUMP_IF_FALSE));
+        c.emit_byte(OpCode::OPPOP);
+    }
+
+    if !c.match_token(TokenType::TokenRightParen) {
+        let body_jump = c.emit_jump(OpCode::OPJUMP);
+        let increment_start = c.code_len();
+        c.expression();
+        c.emit_byte(OpCode::OPPOP);
+        c.consume(TokenType::TokenRightParen, "expect ')' after for clauses");
+
+        c.emit_loop(loop_start);
+        loop_start = increment_start;
+        c.patch_jump(body_jump);
+    }
+
+    statement(c);
+    c.emit_loop(loop_start);
+
+    if let Some(exit_jump) = exit_jump {
+        c.patch_jump(exit_jump);
+        c.emit_byte(OpCode::OPPOP);
+    }
+    c.end_scope();
+}
+fn print_statement(c: &mut Compiler) {
+    c.expression();
+    c.consume(TokenType::TokenSemicolon, "expect ';' after value");
+    c.emit_byte(OpCode::OPPRINT);
+}
+fn return_statement(c: &mut Compiler) {
+    if c.function_kind() == FunctionKind::Script {
+        c.error_with_code(ErrorCode::ReturnOutsideFunction, "can't return from top-level code");
+    }
+    if c.match_token(TokenType::TokenSemicolon) {
+        c.emit_return();
+    } else {
+        if c.function_kind() == FunctionKind::Initializer {
+            c.error_with_code(
+                ErrorCode::ReturnFromInitializer,
+                "can't return a value from an initializer",
+            );
+        }
+        c.expression();
+        c.consume(TokenType::TokenSemicolon, "expect ';' after return value");
+        c.emit_byte(OpCode::OPRETURN);
+    }
+}
+fn expression_statement(c: &mut Compiler) {
+    c.expression();
+    if c.repl && c.function_kind() == FunctionKind::Script && c.state().scope_depth == 0 {
+        if !c.check(TokenType::TokenEof) {
+            c.consume(TokenType::TokenSemicolon, "expect ';' after expression");
+        }
+        c.emit_byte(OpCode::OPPRINT);
+        return;
+    }
+    c.consume(TokenType::TokenSemicolon, "expect ';' after expression");
+    c.emit_byte(OpCode::OPPOP);
+}
@@ -0,0 +1,123 @@
+mod render;
+
+use std::{fmt::Display, ops::Range};
+
+pub use render::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// 诊断的分类, 编号保持稳定以便编辑器和 CI 按编号过滤
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// 词法错误: 非法字符, 未闭合的字符串
+    Lexical,
+    /// 语法错误: 缺少期望的 token
+    Syntax,
+    InvalidAssignmentTarget,
+    /// 同一作用域内重复声明局部变量
+    Redeclaration,
+    /// 在局部变量自身的初始化表达式中读取它
+    UninitializedRead,
+    TooManyLocals,
+    JumpTooLarge,
+    /// 参数或实参个数超过 255
+    TooManyArguments,
+    /// 在顶层代码中使用 `return`
+    ReturnOutsideFunction,
+    /// 单个函数捕获的变量超过 256 个
+    TooManyUpvalues,
+    /// 在类的方法之外使用 `this`
+    ThisOutsideClass,
+    /// 在 `init` 中返回一个值
+    ReturnFromInitializer,
+    /// 类继承自身
+    SelfInheritance,
+    /// 在没有父类的类之外使用 `super`
+    SuperOutsideSubclass,
+    /// 单个函数的常量个数超过操作数能寻址的范围
+    TooManyConstants,
+}
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Lexical => "E0001",
+            ErrorCode::Syntax => "E0002",
+            ErrorCode::InvalidAssignmentTarget => "E0003",
+            ErrorCode::Redeclaration => "E0004",
+            ErrorCode::UninitializedRead => "E0005",
+            ErrorCode::TooManyLocals => "E0006",
+            ErrorCode::JumpTooLarge => "E0007",
+            ErrorCode::TooManyArguments => "E0008",
+            ErrorCode::ReturnOutsideFunction => "E0009",
+            ErrorCode::TooManyUpvalues => "E0010",
+            ErrorCode::ThisOutsideClass => "E0011",
+            ErrorCode::ReturnFromInitializer => "E0012",
+            ErrorCode::SelfInheritance => "E0013",
+            ErrorCode::SuperOutsideSubclass => "E0014",
+            ErrorCode::TooManyConstants => "E0015",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: ErrorCode,
+    pub message: String,
+    /// 从 1 开始的行号
+    pub line: usize,
+    /// 从 1 开始的列号, 按字符计数
+    pub column: usize,
+    /// 源码中的字节范围
+    pub span: Range<usize>,
+}
+impl Diagnostic {
+    /// 行号和列号都由 `span` 的起点算出, 跨行的记号 (如未结束的字符串) 指向它开始的位置
+    pub fn error(code: ErrorCode, message: &str, source: &str, span: Range<usize>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.to_string(),
+            line: line_at(source, span.start),
+            column: column_at(source, span.start),
+            span,
+        }
+    }
+}
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}[{}]: {}",
+            self.line,
+            self.column,
+            self.severity,
+            self.code.as_str(),
+            self.message
+        )
+    }
+}
+
+/// 字节偏移 `offset` 所在的行号
+fn line_at(source: &str, offset: usize) -> usize {
+    let offset = offset.min(source.len());
+    source[..offset].matches('\n').count() + 1
+}
+/// 字节偏移 `offset` 所在的列号
+fn column_at(source: &str, offset: usize) -> usize {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    source[line_start..offset].chars().count() + 1
+}
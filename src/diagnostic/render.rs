@@ -0,0 +1,41 @@
+use super::Diagnostic;
+
+/// 把诊断渲染成带源码片段和 `^` 下划线的多行文本
+///
+/// ```text
+/// error[E0003]: invalid assignment target
+///  --> line 1, column 7
+///   |
+/// 1 | a + b = c;
+///   |       ^
+/// ```
+pub fn render(source: &str, diagnostic: &Diagnostic) -> String {
+    let line_text = source.lines().nth(diagnostic.line.saturating_sub(1)).unwrap_or("");
+    let gutter = diagnostic.line.to_string().len();
+    let pad = " ".repeat(gutter);
+
+    // 下标至少一个字符宽, 且不超出当前行
+    let remaining = line_text.chars().count().saturating_sub(diagnostic.column - 1);
+    let span_width = source
+        .get(diagnostic.span.clone())
+        .map_or(1, |s| s.lines().next().unwrap_or("").chars().count());
+    let width = span_width.min(remaining).max(1);
+
+    let mut out = String::new();
+    out += &format!(
+        "{}[{}]: {}\n",
+        diagnostic.severity,
+        diagnostic.code.as_str(),
+        diagnostic.message
+    );
+    out += &format!("{} --> line {}, column {}\n", pad, diagnostic.line, diagnostic.column);
+    out += &format!("{} |\n", pad);
+    out += &format!("{} | {}\n", diagnostic.line, line_text);
+    out += &format!(
+        "{} | {}{}\n",
+        pad,
+        " ".repeat(diagnostic.column - 1),
+        "^".repeat(width)
+    );
+    out
+}
@@ -1,28 +1,36 @@
-use crate::{chunk::Chunk, Compiler, VM};
+use crate::{diagnostic::Diagnostic, VM};
 
-#[derive(Debug, Clone, Copy)]
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum InterpretErr {
-    CompileError,
-    RuntimeError,
+    /// 编译期收集到的全部诊断
+    CompileError(Vec<Diagnostic>),
+    /// 运行时错误信息, 末尾附带出错的源码行
+    RuntimeError(String),
+}
+impl Display for InterpretErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterpretErr::CompileError(diagnostics) => {
+                for diagnostic in diagnostics {
+                    writeln!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            InterpretErr::RuntimeError(message) => write!(f, "{}", message),
+        }
+    }
 }
 
 pub fn interpret(source: &str) -> Result<(), InterpretErr> {
-    let mut chunk = Chunk::new();
-    let mut compiler = Compiler::new(&mut chunk, source);
-    if !compiler.compile() {
-        return Err(InterpretErr::CompileError);
-    }
-    chunk.disassemble("after compile");
-    let mut vm = VM::new(&chunk);
-    if let Err(e) = vm.run() {
-        return Err(InterpretErr::RuntimeError);
-    }
-    Ok(())
+    let mut vm = VM::new();
+    vm.interpret(source)
 }
 
 #[test]
 fn test() {
-    let source = "nil";
+    let source = "print nil;";
     let r = interpret(source);
     println!("result: {:?}", r);
 }
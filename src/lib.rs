@@ -4,13 +4,23 @@ mod vm;
 mod scanner;
 mod token;
 mod chunk;
-mod parser;
 mod value;
+mod object;
+mod diagnostic;
 mod interpreter;
+mod repl;
+mod loxc;
+mod verify;
 
 pub use helper::*;
 pub use compiler::*;
 pub use vm::*;
 pub use scanner::*;
 pub use token::*;
-pub use interpreter::*;
\ No newline at end of file
+pub use interpreter::*;
+pub use diagnostic::*;
+pub use value::*;
+pub use repl::*;
+pub use loxc::*;
+pub use verify::*;
+pub use object::{Heap, ObjRef};
\ No newline at end of file
@@ -0,0 +1,32 @@
+//! `.loxc` 字节码文件: 编译结果的二进制存档, 所有整数均为小端序
+//!
+//! ```text
+//! file     := magic "LOXC" | version: u16 | function
+//! function := has_name: u8 | [name: string] | arity: u8 | upvalue_count: u16
+//!             | code_len: u32 | code: [u8]
+//!             | run_count: u32 | runs: [start: u32 | line: u32]
+//!             | constant_count: u32 | constants: [constant]
+//! constant := 0: u8 | number: f64
+//!           | 1: u8 | string
+//!           | 2: u8 | function
+//!           | 3: u8                    (nil)
+//!           | 4: u8 | bool: u8
+//! string   := len: u32 | utf-8 bytes
+//! ```
+//!
+//! 加载时会拒绝魔数不符, 版本不符, 被截断, 常量标签未知, 字符串不是合法 UTF-8,
+//! 行号表不合法以及末尾有多余数据的文件; 结构完好的文件还要通过字节码校验
+mod reader;
+mod writer;
+
+pub use reader::*;
+pub use writer::*;
+
+pub const MAGIC: &[u8; 4] = b"LOXC";
+pub const VERSION: u16 = 1;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+const TAG_NIL: u8 = 3;
+const TAG_BOOL: u8 = 4;
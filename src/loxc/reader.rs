@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+use crate::{
+    chunk::{Chunk, LineRun, LineTable},
+    object::{Heap, Obj, ObjFunction, ObjRef},
+    value::Value,
+    verify::{verify_function, VerifyError},
+};
+
+use super::{MAGIC, TAG_BOOL, TAG_FUNCTION, TAG_NIL, TAG_NUMBER, TAG_STRING, VERSION};
+
+/// 函数原型允许的最大嵌套层数, 防止恶意文件耗尽加载器的调用栈
+const NESTING_MAX: usize = 256;
+
+/// 加载 `.loxc` 文件失败的原因; `offset` 为出错位置在文件中的字节偏移
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoxcError {
+    /// 文件不以 "LOXC" 开头
+    BadMagic,
+    UnsupportedVersion { found: u16, expected: u16 },
+    /// 文件在读完之前就结束了
+    Truncated { offset: usize },
+    InvalidConstantTag { tag: u8, offset: usize },
+    InvalidUtf8 { offset: usize },
+    /// 行号表的游程起点必须从 0 开始严格递增并落在代码范围内
+    InvalidLineTable { offset: usize },
+    NestingTooDeep { offset: usize },
+    /// 顶层函数之后还有多余的字节
+    TrailingBytes { offset: usize },
+    /// 文件结构完好, 但字节码没有通过校验
+    InvalidBytecode(VerifyError),
+}
+impl Display for LoxcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxcError::BadMagic => write!(f, "not a loxc file (bad magic bytes)"),
+            LoxcError::UnsupportedVersion { found, expected } => write!(
+                f,
+                "unsupported loxc version {} (this build reads version {})",
+                found, expected
+            ),
+            LoxcError::Truncated { offset } => write!(f, "truncated file at byte {}", offset),
+            LoxcError::InvalidConstantTag { tag, offset } => {
+                write!(f, "invalid constant tag {} at byte {}", tag, offset)
+            }
+            LoxcError::InvalidUtf8 { offset } => write!(f, "invalid utf-8 string at byte {}", offset),
+            LoxcError::InvalidLineTable { offset } => write!(f, "invalid line table at byte {}", offset),
+            LoxcError::NestingTooDeep { offset } => {
+                write!(f, "functions nested too deeply at byte {}", offset)
+            }
+            LoxcError::TrailingBytes { offset } => write!(f, "unexpected data after byte {}", offset),
+            LoxcError::InvalidBytecode(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// 解析 `.loxc` 文件内容, 把其中的函数原型和字符串分配到堆上, 返回顶层函数
+///
+/// 文件中的字节码来自外部, 返回前会先通过校验器检查
+pub fn read_loxc(heap: &mut Heap, bytes: &[u8]) -> Result<ObjRef, LoxcError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(LoxcError::BadMagic);
+    }
+    let mut reader = Reader {
+        heap,
+        bytes,
+        offset: MAGIC.len(),
+    };
+    let version = reader.u16()? as u16;
+    if version != VERSION {
+        return Err(LoxcError::UnsupportedVersion {
+            found: version,
+            expected: VERSION,
+        });
+    }
+    let function = reader.function(0)?;
+    if reader.offset != bytes.len() {
+        return Err(LoxcError::TrailingBytes { offset: reader.offset });
+    }
+    verify_function(heap, function).map_err(LoxcError::InvalidBytecode)?;
+    Ok(function)
+}
+
+struct Reader<'a> {
+    heap: &'a mut Heap,
+    bytes: &'a [u8],
+    offset: usize,
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LoxcError> {
+        let truncated = LoxcError::Truncated { offset: self.bytes.len() };
+        let end = self.offset.checked_add(len).ok_or(truncated.clone())?;
+        let slice = self.bytes.get(self.offset..end).ok_or(truncated)?;
+        self.offset = end;
+        Ok(slice)
+    }
+    fn u8(&mut self) -> Result<u8, LoxcError> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<usize, LoxcError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+    }
+    fn u32(&mut self) -> Result<usize, LoxcError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+    fn f64(&mut self) -> Result<f64, LoxcError> {
+        let bytes = self.take(8)?;
+        Ok(f64::from_le_bytes(bytes.try_into().expect("took 8 bytes")))
+    }
+    fn string(&mut self) -> Result<ObjRef, LoxcError> {
+        let len = self.u32()?;
+        let start = self.offset;
+        let bytes = self.take(len)?;
+        let chars = std::str::from_utf8(bytes).map_err(|_| LoxcError::InvalidUtf8 { offset: start })?;
+        Ok(self.heap.intern(chars))
+    }
+    fn function(&mut self, depth: usize) -> Result<ObjRef, LoxcError> {
+        if depth > NESTING_MAX {
+            return Err(LoxcError::NestingTooDeep { offset: self.offset });
+        }
+        let name = match self.u8()? {
+            0 => None,
+            _ => Some(self.string()?),
+        };
+        let mut function = ObjFunction::new(name);
+        function.arity = self.u8()? as usize;
+        function.upvalue_count = self.u16()?;
+
+        let code_len = self.u32()?;
+        let code = self.take(code_len)?.to_vec();
+        let lines = self.line_table(code.len())?;
+
+        let constant_count = self.u32()?;
+        let mut constants = vec![];
+        for _ in 0..constant_count {
+            constants.push(self.constant(depth)?);
+        }
+        function.chunk = Chunk::from_parts(code, lines, constants);
+        Ok(self.heap.alloc(Obj::Function(function)))
+    }
+    fn line_table(&mut self, code_len: usize) -> Result<LineTable, LoxcError> {
+        let offset = self.offset;
+        let run_count = self.u32()?;
+        let mut runs: Vec<LineRun> = vec![];
+        for _ in 0..run_count {
+            let start = self.u32()?;
+            let line = self.u32()?;
+            let valid = match runs.last() {
+                None => start == 0,
+                Some(last) => start > last.start,
+            };
+            if !valid || start >= code_len {
+                return Err(LoxcError::InvalidLineTable { offset });
+            }
+            runs.push(LineRun { start, line });
+        }
+        if runs.is_empty() && code_len > 0 {
+            return Err(LoxcError::InvalidLineTable { offset });
+        }
+        Ok(LineTable::from_runs(runs))
+    }
+    fn constant(&mut self, depth: usize) -> Result<Value, LoxcError> {
+        let offset = self.offset;
+        match self.u8()? {
+            TAG_NUMBER => Ok(Value::Number(self.f64()?)),
+            TAG_STRING => Ok(Value::Obj(self.string()?)),
+            TAG_FUNCTION => Ok(Value::Obj(self.function(depth + 1)?)),
+            TAG_NIL => Ok(Value::Nil),
+            TAG_BOOL => Ok(Value::Bool(self.u8()? != 0)),
+            tag => Err(LoxcError::InvalidConstantTag { tag, offset }),
+        }
+    }
+}
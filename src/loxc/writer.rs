@@ -0,0 +1,86 @@
+use crate::{
+    object::{Heap, Obj, ObjFunction, ObjRef},
+    value::Value,
+};
+
+use super::{MAGIC, TAG_BOOL, TAG_FUNCTION, TAG_NIL, TAG_NUMBER, TAG_STRING, VERSION};
+
+/// 把顶层函数及其嵌套的函数原型序列化成 `.loxc` 文件内容
+pub fn write_loxc(heap: &Heap, function: ObjRef) -> Vec<u8> {
+    let mut writer = Writer { heap, out: vec![] };
+    writer.out.extend_from_slice(MAGIC);
+    writer.u16(VERSION as usize);
+    writer.function(heap.as_function(function).expect("not a function"));
+    writer.out
+}
+
+struct Writer<'a> {
+    heap: &'a Heap,
+    out: Vec<u8>,
+}
+impl Writer<'_> {
+    fn u8(&mut self, n: u8) {
+        self.out.push(n);
+    }
+    fn u16(&mut self, n: usize) {
+        self.out.extend_from_slice(&(n as u16).to_le_bytes());
+    }
+    fn u32(&mut self, n: usize) {
+        self.out.extend_from_slice(&(n as u32).to_le_bytes());
+    }
+    fn string(&mut self, r: ObjRef) {
+        let chars = &self.heap.as_string(r).expect("not a string").chars;
+        self.u32(chars.len());
+        self.out.extend_from_slice(chars.as_bytes());
+    }
+    fn function(&mut self, function: &ObjFunction) {
+        match function.name {
+            Some(name) => {
+                self.u8(1);
+                self.string(name);
+            }
+            None => self.u8(0),
+        }
+        self.u8(function.arity as u8);
+        self.u16(function.upvalue_count);
+
+        let chunk = &function.chunk;
+        self.u32(chunk.code.len());
+        self.out.extend_from_slice(&chunk.code);
+        let runs = chunk.lines.runs();
+        self.u32(runs.len());
+        for run in runs {
+            self.u32(run.start);
+            self.u32(run.line);
+        }
+        self.u32(chunk.constants.len());
+        for constant in &chunk.constants {
+            self.constant(*constant);
+        }
+    }
+    fn constant(&mut self, value: Value) {
+        match value {
+            Value::Number(n) => {
+                self.u8(TAG_NUMBER);
+                self.out.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Obj(r) => match self.heap.get(r) {
+                Obj::String(_) => {
+                    self.u8(TAG_STRING);
+                    self.string(r);
+                }
+                Obj::Function(function) => {
+                    self.u8(TAG_FUNCTION);
+                    self.function(function);
+                }
+                other => unreachable!("{} in constant pool", other.kind()),
+            },
+            // 编译器用 OPNIL/OPTRUE/OPFALSE 加载字面量, 不会产生这两种常量; 格式仍然支持它们
+            Value::Nil => self.u8(TAG_NIL),
+            Value::Bool(b) => {
+                self.u8(TAG_BOOL);
+                self.u8(b as u8);
+            }
+        }
+    }
+}
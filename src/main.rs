@@ -1,20 +1,115 @@
-use std::fs;
+use std::{fs, path::Path, process};
 
 use clap::Parser;
-use cmd_parser::CmdParser;
-use lox_vm_rust::interpret;
+use cmd_parser::{CmdParser, Command};
+use lox_vm_rust::{dump_tokens, render, InterpretErr, Repl, Script, MAGIC, VM};
 
 mod cmd_parser;
+
+// sysexits.h 中的退出码
+/// 输入数据有误: 编译错误
+const EX_DATAERR: i32 = 65;
+/// 内部错误: 运行时错误
+const EX_SOFTWARE: i32 = 70;
+/// 读写文件失败
+const EX_IOERR: i32 = 74;
+
 fn main() {
     let args = CmdParser::parse();
-    match args.file {
-        Some(file) => {
-            if !file.exists() {
-                panic!("file({:?}) doesn't exsit", file)
+    let mut vm = VM::new();
+    vm.set_trace(args.trace);
+    vm.set_dump_bytecode(args.dump_bytecode);
+
+    let result = match (args.command, args.file) {
+        (Some(Command::Run { file }), _) | (None, Some(file)) => run(&mut vm, &file),
+        (Some(Command::Check { file }), _) => check(&mut vm, &file),
+        (Some(Command::Disasm { file }), _) => disasm(&mut vm, &file),
+        (Some(Command::Tokens { file }), _) => read(&file).map(|source| print!("{}", dump_tokens(&source))),
+        (Some(Command::Compile { file, output }), _) => {
+            let output = output.unwrap_or_else(|| file.with_extension("loxc"));
+            compile(&mut vm, &file, &output)
+        }
+        (None, None) => Repl::with_vm(vm).run().map_err(|e| {
+            eprintln!("{}", e);
+            EX_IOERR
+        }),
+    };
+    if let Err(code) = result {
+        process::exit(code);
+    }
+}
+
+fn read(path: &Path) -> Result<String, i32> {
+    fs::read_to_string(path).map_err(|e| {
+        eprintln!("can't read '{}': {}", path.display(), e);
+        EX_IOERR
+    })
+}
+
+/// 输出错误信息, 返回对应的退出码
+fn report(source: &str, e: InterpretErr) -> i32 {
+    match e {
+        InterpretErr::CompileError(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprint!("{}", render(source, diagnostic));
             }
-            let contents = fs::read_to_string(file).expect("read file error");
-            interpret(&contents).expect("interpret error");
+            EX_DATAERR
+        }
+        InterpretErr::RuntimeError(message) => {
+            eprintln!("{}", message);
+            EX_SOFTWARE
         }
-        None => println!("rep"),
     }
 }
+
+fn read_bytes(path: &Path) -> Result<Vec<u8>, i32> {
+    fs::read(path).map_err(|e| {
+        eprintln!("can't read '{}': {}", path.display(), e);
+        EX_IOERR
+    })
+}
+
+/// 以魔数识别 `.loxc` 文件并加载; 不是字节码文件时返回 `None`
+fn load(vm: &mut VM, path: &Path) -> Result<Option<Script>, i32> {
+    let bytes = read_bytes(path)?;
+    if !bytes.starts_with(MAGIC) {
+        return Ok(None);
+    }
+    vm.load_loxc(&bytes).map(Some).map_err(|e| {
+        eprintln!("can't load '{}': {}", path.display(), e);
+        EX_DATAERR
+    })
+}
+
+fn run(vm: &mut VM, path: &Path) -> Result<(), i32> {
+    if let Some(script) = load(vm, path)? {
+        return vm.run_function(&script).map_err(|e| report("", e));
+    }
+    let source = read(path)?;
+    vm.interpret(&source).map_err(|e| report(&source, e))
+}
+
+fn check(vm: &mut VM, path: &Path) -> Result<(), i32> {
+    let source = read(path)?;
+    vm.compile(&source).map(|_| ()).map_err(|e| report(&source, e))
+}
+
+fn disasm(vm: &mut VM, path: &Path) -> Result<(), i32> {
+    if let Some(script) = load(vm, path)? {
+        print!("{}", vm.disassemble_function(&script));
+        return Ok(());
+    }
+    let source = read(path)?;
+    let text = vm.disassemble(&source).map_err(|e| report(&source, e))?;
+    print!("{}", text);
+    Ok(())
+}
+
+fn compile(vm: &mut VM, path: &Path, output: &Path) -> Result<(), i32> {
+    let source = read(path)?;
+    let bytes = vm.compile_to_loxc(&source).map_err(|e| report(&source, e))?;
+    fs::write(output, bytes).map_err(|e| {
+        eprintln!("can't write '{}': {}", output.display(), e);
+        EX_IOERR
+    })
+}
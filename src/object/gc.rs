@@ -0,0 +1,111 @@
+use crate::value::Value;
+
+use super::{Heap, Obj, ObjRef, ObjUpvalue};
+
+/// 每轮回收后, 下次触发回收的阈值为存活字节数乘以该系数
+const GC_HEAP_GROW_FACTOR: usize = 2;
+
+/// 三色标记清除: 白色为未标记, 灰色为已标记但在 `gray` 中等待遍历, 黑色为已遍历
+impl Heap {
+    pub fn mark_value(&mut self, value: Value) {
+        if let Value::Obj(r) = value {
+            self.mark_object(r);
+        }
+    }
+    pub fn mark_object(&mut self, r: ObjRef) {
+        let Some(entry) = self.objects[r.0].as_mut() else {
+            panic!("marking freed object {:?}", r);
+        };
+        if entry.marked {
+            return;
+        }
+        entry.marked = true;
+        if self.log_gc {
+            eprintln!("{:?} mark {}", r, entry.obj.kind());
+        }
+        self.gray.push(r);
+    }
+    /// 从根集合出发标记完成后调用: 遍历灰色对象直到没有新的可达对象, 再释放白色对象
+    pub fn collect(&mut self) {
+        let before = self.bytes_allocated;
+        self.trace_references();
+        self.remove_white_strings();
+        self.sweep();
+        self.next_gc = self.bytes_allocated.max(1) * GC_HEAP_GROW_FACTOR;
+        if self.log_gc {
+            eprintln!(
+                "-- gc end: collected {} bytes (from {} to {}) next at {}",
+                before - self.bytes_allocated,
+                before,
+                self.bytes_allocated,
+                self.next_gc
+            );
+        }
+    }
+    fn trace_references(&mut self) {
+        while let Some(r) = self.gray.pop() {
+            self.blacken_object(r);
+        }
+    }
+    fn blacken_object(&mut self, r: ObjRef) {
+        let mut children: Vec<Value> = vec![];
+        match self.get(r) {
+            Obj::String(_) => {}
+            Obj::Function(f) => {
+                children.extend(f.name.map(Value::Obj));
+                children.extend(f.chunk.constants.iter().copied());
+            }
+            Obj::Closure(c) => {
+                children.push(Value::Obj(c.function));
+                children.extend(c.upvalues.iter().copied().map(Value::Obj));
+            }
+            Obj::Upvalue(ObjUpvalue::Closed(value)) => children.push(*value),
+            Obj::Upvalue(ObjUpvalue::Open(_)) => {}
+            Obj::Native(n) => children.push(Value::Obj(n.name)),
+            Obj::Class(c) => {
+                children.push(Value::Obj(c.name));
+                for (&name, &method) in &c.methods {
+                    children.push(Value::Obj(name));
+                    children.push(method);
+                }
+            }
+            Obj::BoundMethod(b) => {
+                children.push(b.receiver);
+                children.push(Value::Obj(b.method));
+            }
+            Obj::Instance(i) => {
+                children.push(Value::Obj(i.class));
+                for (&name, &value) in &i.fields {
+                    children.push(Value::Obj(name));
+                    children.push(value);
+                }
+            }
+        }
+        for child in children {
+            self.mark_value(child);
+        }
+    }
+    /// 驻留表对字符串是弱引用: 不可达的字符串要在清除前从表中移除
+    fn remove_white_strings(&mut self) {
+        let objects = &self.objects;
+        self.strings
+            .retain(|_, r| objects[r.0].as_ref().is_some_and(|entry| entry.marked));
+    }
+    fn sweep(&mut self) {
+        for (idx, slot) in self.objects.iter_mut().enumerate() {
+            let Some(entry) = slot else {
+                continue;
+            };
+            if entry.marked {
+                entry.marked = false;
+                continue;
+            }
+            if self.log_gc {
+                eprintln!("{:?} free {}", ObjRef(idx), entry.obj.kind());
+            }
+            self.bytes_allocated -= entry.size;
+            *slot = None;
+            self.free.push(idx);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::value::Value;
+
+use super::{
+    Obj, ObjBoundMethod, ObjClass, ObjClosure, ObjFunction, ObjInstance, ObjNative, ObjRef, ObjString, ObjUpvalue,
+};
+
+/// 首次触发垃圾回收的堆大小
+const INITIAL_NEXT_GC: usize = 1024 * 1024;
+
+pub(super) struct HeapEntry {
+    pub obj: Obj,
+    pub marked: bool,
+    /// 分配时估算的字节数, 释放时从 `bytes_allocated` 中扣除
+    pub size: usize,
+}
+
+pub struct Heap {
+    /// 对象槽位; `None` 为已释放的槽位, 下标记录在 `free` 中等待复用
+    pub(super) objects: Vec<Option<HeapEntry>>,
+    pub(super) free: Vec<usize>,
+    /// 字符串驻留表: 相同内容的字符串只分配一次, 相等比较退化为句柄比较;
+    /// 键与 `ObjString` 共用同一份字符数据
+    pub(super) strings: HashMap<Rc<str>, ObjRef>,
+    pub(super) bytes_allocated: usize,
+    pub(super) next_gc: usize,
+    /// 已标记但引用尚未遍历的对象
+    pub(super) gray: Vec<ObjRef>,
+    /// 每次分配前都进行回收, 用于在测试中暴露 GC 问题
+    pub stress_gc: bool,
+    /// 在标准错误输出上报告分配、释放和每轮回收的统计
+    pub log_gc: bool,
+}
+impl Heap {
+    pub fn new() -> Self {
+        Self {
+            objects: vec![],
+            free: vec![],
+            strings: HashMap::new(),
+            bytes_allocated: 0,
+            next_gc: INITIAL_NEXT_GC,
+            gray: vec![],
+            stress_gc: false,
+            log_gc: false,
+        }
+    }
+    /// 分配一个对象; 本身从不触发回收, 调用方应先通过 `should_collect` 检查并回收
+    pub fn alloc(&mut self, obj: Obj) -> ObjRef {
+        let size = obj.size();
+        self.bytes_allocated += size;
+        let entry = HeapEntry {
+            obj,
+            marked: false,
+            size,
+        };
+        let r = match self.free.pop() {
+            Some(idx) => {
+                self.objects[idx] = Some(entry);
+                ObjRef(idx)
+            }
+            None => {
+                self.objects.push(Some(entry));
+                ObjRef(self.objects.len() - 1)
+            }
+        };
+        if self.log_gc {
+            eprintln!("{:?} allocate {} bytes for {}", r, size, self.get(r).kind());
+        }
+        r
+    }
+    pub fn should_collect(&self) -> bool {
+        self.stress_gc || self.bytes_allocated > self.next_gc
+    }
+    pub fn bytes_allocated(&self) -> usize {
+        self.bytes_allocated
+    }
+    pub fn live_objects(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+    /// 返回 `chars` 对应的驻留字符串, 不存在时复制一份并登记
+    pub fn intern(&mut self, chars: &str) -> ObjRef {
+        if let Some(r) = self.strings.get(chars) {
+            return *r;
+        }
+        self.take_string(chars.to_string())
+    }
+    /// 同 `intern`, 但直接接管 `chars` 的所有权, 用于运行时拼接出的新字符串
+    pub fn take_string(&mut self, chars: String) -> ObjRef {
+        if let Some(r) = self.strings.get(chars.as_str()) {
+            return *r;
+        }
+        let chars: Rc<str> = chars.into();
+        let r = self.alloc(Obj::String(ObjString {
+            chars: Rc::clone(&chars),
+        }));
+        self.strings.insert(chars, r);
+        r
+    }
+    /// 对象内的字段表或方法表增长后重新估算它的大小, 让回收阈值反映真实的堆大小
+    pub fn resize(&mut self, r: ObjRef) {
+        let Some(entry) = self.objects[r.0].as_mut() else {
+            panic!("resizing freed object {:?}", r);
+        };
+        let size = entry.obj.size();
+        self.bytes_allocated = self.bytes_allocated - entry.size + size;
+        entry.size = size;
+    }
+    pub fn get(&self, r: ObjRef) -> &Obj {
+        match &self.objects[r.0] {
+            Some(entry) => &entry.obj,
+            None => panic!("use of freed object {:?}", r),
+        }
+    }
+    pub fn get_mut(&mut self, r: ObjRef) -> &mut Obj {
+        match &mut self.objects[r.0] {
+            Some(entry) => &mut entry.obj,
+            None => panic!("use of freed object {:?}", r),
+        }
+    }
+    pub fn as_string(&self, r: ObjRef) -> Option<&ObjString> {
+        match self.get(r) {
+            Obj::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    pub fn as_function(&self, r: ObjRef) -> Option<&ObjFunction> {
+        match self.get(r) {
+            Obj::Function(f) => Some(f),
+            _ => None,
+        }
+    }
+    pub fn as_closure(&self, r: ObjRef) -> Option<&ObjClosure> {
+        match self.get(r) {
+            Obj::Closure(c) => Some(c),
+            _ => None,
+        }
+    }
+    pub fn as_upvalue(&self, r: ObjRef) -> Option<&ObjUpvalue> {
+        match self.get(r) {
+            Obj::Upvalue(u) => Some(u),
+            _ => None,
+        }
+    }
+    pub fn as_upvalue_mut(&mut self, r: ObjRef) -> Option<&mut ObjUpvalue> {
+        match self.get_mut(r) {
+            Obj::Upvalue(u) => Some(u),
+            _ => None,
+        }
+    }
+    pub fn as_native(&self, r: ObjRef) -> Option<&ObjNative> {
+        match self.get(r) {
+            Obj::Native(n) => Some(n),
+            _ => None,
+        }
+    }
+    pub fn as_class(&self, r: ObjRef) -> Option<&ObjClass> {
+        match self.get(r) {
+            Obj::Class(c) => Some(c),
+            _ => None,
+        }
+    }
+    pub fn as_instance(&self, r: ObjRef) -> Option<&ObjInstance> {
+        match self.get(r) {
+            Obj::Instance(i) => Some(i),
+            _ => None,
+        }
+    }
+    pub fn as_instance_mut(&mut self, r: ObjRef) -> Option<&mut ObjInstance> {
+        match self.get_mut(r) {
+            Obj::Instance(i) => Some(i),
+            _ => None,
+        }
+    }
+    pub fn as_bound_method(&self, r: ObjRef) -> Option<&ObjBoundMethod> {
+        match self.get(r) {
+            Obj::BoundMethod(b) => Some(b),
+            _ => None,
+        }
+    }
+    pub fn function_name(&self, function: &ObjFunction) -> String {
+        match function.name {
+            Some(name) => self.format_value(Value::Obj(name)),
+            None => "script".to_string(),
+        }
+    }
+    /// 按 Lox 的 `print` 语义格式化一个值
+    pub fn format_value(&self, value: Value) -> String {
+        match value {
+            Value::Number(n) => format!("{}", n),
+            Value::Bool(b) => format!("{}", b),
+            Value::Nil => "nil".to_string(),
+            Value::Obj(r) => match self.get(r) {
+                Obj::String(s) => s.chars.to_string(),
+                Obj::Function(f) => match f.name {
+                    Some(_) => format!("<fn {}>", self.function_name(f)),
+                    None => "<script>".to_string(),
+                },
+                Obj::Closure(c) => self.format_value(Value::Obj(c.function)),
+                Obj::Upvalue(_) => "upvalue".to_string(),
+                Obj::Native(_) => "<native fn>".to_string(),
+                Obj::Class(c) => self.format_value(Value::Obj(c.name)),
+                Obj::Instance(i) => {
+                    let class = self.as_class(i.class).expect("instance of a non-class");
+                    format!("{} instance", self.format_value(Value::Obj(class.name)))
+                }
+                Obj::BoundMethod(b) => self.format_value(Value::Obj(b.method)),
+            },
+        }
+    }
+}
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+#[test]
+fn test() {
+    let mut heap = Heap::new();
+    let a = heap.intern("lox");
+    let b = heap.take_string(String::from("lox"));
+    let c = heap.intern("vm");
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+}
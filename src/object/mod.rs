@@ -0,0 +1,165 @@
+mod gc;
+mod heap;
+
+pub use heap::*;
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    chunk::Chunk,
+    value::Value,
+    vm::{Arity, NativeFn},
+};
+
+/// 堆对象句柄, 指向 `Heap` 中的一个槽位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(usize);
+
+#[derive(Debug)]
+pub enum Obj {
+    String(ObjString),
+    Function(ObjFunction),
+    Closure(ObjClosure),
+    Upvalue(ObjUpvalue),
+    Native(ObjNative),
+    Class(ObjClass),
+    Instance(ObjInstance),
+    BoundMethod(ObjBoundMethod),
+}
+
+impl Obj {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Obj::String(_) => "string",
+            Obj::Function(_) => "function",
+            Obj::Closure(_) => "closure",
+            Obj::Upvalue(_) => "upvalue",
+            Obj::Native(_) => "native",
+            Obj::Class(_) => "class",
+            Obj::Instance(_) => "instance",
+            Obj::BoundMethod(_) => "bound method",
+        }
+    }
+    /// 估算对象占用的字节数, 用于决定何时触发回收
+    pub fn size(&self) -> usize {
+        let payload = match self {
+            Obj::String(s) => s.chars.len(),
+            Obj::Function(f) => {
+                f.chunk.code.len()
+                    + std::mem::size_of_val(f.chunk.lines.runs())
+                    + f.chunk.constants.len() * std::mem::size_of::<Value>()
+            }
+            Obj::Closure(c) => c.upvalues.len() * std::mem::size_of::<ObjRef>(),
+            Obj::Instance(i) => i.fields.len() * std::mem::size_of::<(ObjRef, Value)>(),
+            Obj::Class(c) => c.methods.len() * std::mem::size_of::<(ObjRef, Value)>(),
+            Obj::Upvalue(_) | Obj::Native(_) | Obj::BoundMethod(_) => 0,
+        };
+        std::mem::size_of::<Obj>() + payload
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjString {
+    /// 与驻留表的键共享
+    pub chars: Rc<str>,
+}
+
+#[derive(Debug)]
+pub struct ObjFunction {
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+    /// 函数名, 顶层脚本为 `None`
+    pub name: Option<ObjRef>,
+}
+impl ObjFunction {
+    pub fn new(name: Option<ObjRef>) -> Self {
+        Self {
+            arity: 0,
+            upvalue_count: 0,
+            chunk: Chunk::new(),
+            name,
+        }
+    }
+}
+
+/// 运行时的函数值: 函数原型加上它捕获的变量
+#[derive(Debug)]
+pub struct ObjClosure {
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+impl ObjClosure {
+    pub fn new(function: ObjRef) -> Self {
+        Self {
+            function,
+            upvalues: vec![],
+        }
+    }
+}
+
+/// 被闭包捕获的变量
+#[derive(Debug, Clone, Copy)]
+pub enum ObjUpvalue {
+    /// 变量仍在栈上, 记录其栈槽位
+    Open(usize),
+    /// 变量所在的作用域已结束, 值被搬到堆上
+    Closed(Value),
+}
+
+/// 由宿主提供的函数
+pub struct ObjNative {
+    pub name: ObjRef,
+    pub arity: Arity,
+    pub function: NativeFn,
+}
+impl std::fmt::Debug for ObjNative {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjNative")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjClass {
+    pub name: ObjRef,
+    /// 方法名到闭包的映射
+    pub methods: HashMap<ObjRef, Value>,
+}
+impl ObjClass {
+    pub fn new(name: ObjRef) -> Self {
+        Self {
+            name,
+            methods: HashMap::new(),
+        }
+    }
+}
+
+/// 类的实例, 字段在运行时按需添加
+#[derive(Debug)]
+pub struct ObjInstance {
+    pub class: ObjRef,
+    pub fields: HashMap<ObjRef, Value>,
+}
+impl ObjInstance {
+    pub fn new(class: ObjRef) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+}
+
+/// 作为值读取的方法, 记住了它的接收者
+#[derive(Debug)]
+pub struct ObjBoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
+impl ObjBoundMethod {
+    pub fn new(receiver: Value, method: ObjRef) -> Self {
+        Self { receiver, method }
+    }
+}
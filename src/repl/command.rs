@@ -0,0 +1,66 @@
+use std::{fmt::Write, fs, time::Instant};
+
+use crate::dump_tokens;
+
+use super::{report, Repl};
+
+const HELP: &str = "\
+:tokens <code>  print the tokens scanned from <code>
+:dis <code>     compile <code> and print its bytecode without running it
+:stack          print the VM stack as it was at the last runtime error
+:time <code>    run <code> and report wall time and instruction count
+:load <file>    run a file in the current session
+:help           print this message
+";
+
+impl Repl {
+    /// 执行一条以 `:` 开头的元命令, 返回要展示给用户的文本
+    pub fn command(&mut self, input: &str) -> Result<String, String> {
+        let input = input.trim().strip_prefix(':').unwrap_or(input);
+        let (name, arg) = match input.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (input, ""),
+        };
+        match name {
+            "tokens" => Ok(dump_tokens(arg)),
+            "dis" => self.dis(arg),
+            "stack" => Ok(self.stack()),
+            "time" => self.time(arg),
+            "load" => self.load(arg),
+            "help" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command ':{}', try :help\n", name)),
+        }
+    }
+    fn dis(&mut self, source: &str) -> Result<String, String> {
+        self.vm.disassemble_repl(source).map_err(|e| report(source, e))
+    }
+    /// 每次输入都会执行到顶层返回, 栈在两次输入之间总是空的; 因此展示最近一次出错时留下的快照
+    fn stack(&self) -> String {
+        let stack = self.vm.error_stack();
+        if stack.is_empty() {
+            return "(empty)\n".to_string();
+        }
+        let mut text = String::new();
+        for value in stack {
+            let _ = write!(text, "[ {} ]", value);
+        }
+        text + "\n"
+    }
+    fn time(&mut self, source: &str) -> Result<String, String> {
+        let before = self.vm.instruction_count();
+        let start = Instant::now();
+        self.eval(source)?;
+        let elapsed = start.elapsed();
+        let instructions = self.vm.instruction_count() - before;
+        Ok(format!("elapsed: {:?}, instructions: {}\n", elapsed, instructions))
+    }
+    fn load(&mut self, path: &str) -> Result<String, String> {
+        if path.is_empty() {
+            return Err("usage: :load <file>\n".to_string());
+        }
+        let source = fs::read_to_string(path).map_err(|e| format!("can't read '{}': {}\n", path, e))?;
+        // 文件按普通脚本执行, 其中定义的全局变量留在当前会话中
+        self.vm.interpret(&source).map_err(|e| report(&source, e))?;
+        Ok(String::new())
+    }
+}
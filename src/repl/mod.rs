@@ -0,0 +1,117 @@
+mod command;
+
+use std::{env, path::PathBuf};
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use crate::{diagnostic::render, InterpretErr, ScanError, Scanner, TokenType, VM};
+
+const PROMPT: &str = "> ";
+/// 输入未完整时的续行提示符
+const CONTINUATION_PROMPT: &str = "... ";
+const HISTORY_FILE: &str = ".lox_history";
+
+/// 交互式解释器; 所有输入在同一个 VM 中执行, 全局变量在多次输入之间保留
+pub struct Repl {
+    vm: VM,
+}
+impl Repl {
+    pub fn new() -> Self {
+        Self::with_vm(VM::new())
+    }
+    pub fn with_vm(vm: VM) -> Self {
+        Self { vm }
+    }
+    /// 执行一条完整的输入; 出错时返回可以直接展示给用户的错误信息
+    pub fn eval(&mut self, source: &str) -> Result<(), String> {
+        self.vm.interpret_repl(source).map_err(|e| report(source, e))
+    }
+    /// 读取-执行-打印循环, 直到输入结束 (Ctrl-D)
+    pub fn run(&mut self) -> rustyline::Result<()> {
+        let mut editor = DefaultEditor::new()?;
+        let history = history_path();
+        // 首次运行时历史文件还不存在
+        let _ = editor.load_history(&history);
+        let result = self.read_eval_loop(&mut editor);
+        // 读入出错退出时也保留本次会话的历史
+        let saved = editor.save_history(&history);
+        result.and(saved)
+    }
+    fn read_eval_loop(&mut self, editor: &mut DefaultEditor) -> rustyline::Result<()> {
+        let mut buffer = String::new();
+        loop {
+            let prompt = if buffer.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    buffer += &line;
+                    buffer.push('\n');
+                    if !is_complete(&buffer) {
+                        continue;
+                    }
+                    let entry = std::mem::take(&mut buffer);
+                    if entry.trim().is_empty() {
+                        continue;
+                    }
+                    editor.add_history_entry(entry.trim_end())?;
+                    let result = if entry.trim_start().starts_with(':') {
+                        self.command(&entry).map(|text| print!("{}", text))
+                    } else {
+                        self.eval(&entry)
+                    };
+                    if let Err(message) = result {
+                        eprint!("{}", message);
+                    }
+                }
+                // Ctrl-C 丢弃当前尚未完成的输入
+                Err(ReadlineError::Interrupted) => buffer.clear(),
+                Err(ReadlineError::Eof) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 把解释错误转换成展示给用户的文本
+fn report(source: &str, e: InterpretErr) -> String {
+    match e {
+        InterpretErr::CompileError(diagnostics) => diagnostics
+            .iter()
+            .map(|diagnostic| render(source, diagnostic))
+            .collect(),
+        InterpretErr::RuntimeError(message) => message + "\n",
+    }
+}
+
+/// 历史文件放在用户主目录下, 可以用 `LOX_HISTORY` 覆盖
+fn history_path() -> PathBuf {
+    if let Some(path) = env::var_os("LOX_HISTORY") {
+        return PathBuf::from(path);
+    }
+    match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(HISTORY_FILE),
+        None => PathBuf::from(HISTORY_FILE),
+    }
+}
+
+/// 输入是否完整: 圆括号或花括号未闭合, 或者字符串未结束时需要继续读入下一行
+pub fn is_complete(source: &str) -> bool {
+    let mut scanner = Scanner::new(source);
+    let mut depth: isize = 0;
+    loop {
+        let token = scanner.scan_token();
+        match token.t_type {
+            TokenType::TokenLeftParen | TokenType::TokenLeftBrace => depth += 1,
+            TokenType::TokenRightParen | TokenType::TokenRightBrace => depth -= 1,
+            TokenType::TokenError if token.error == Some(ScanError::UnterminatedString) => return false,
+            TokenType::TokenEof => break,
+            _ => {}
+        }
+    }
+    // 多余的右括号交给编译器报错
+    depth <= 0
+}
@@ -1,8 +1,8 @@
-use std::{iter::Peekable, str::Chars};
+use std::{fmt::Write, str::Chars};
 
 use crate::{
     keyword_match,
-    token::{Token, TokenType},
+    token::{ScanError, Token, TokenType},
     MyPeekable,
 };
 use TokenType::*;
@@ -24,10 +24,10 @@ impl<'a> Scanner<'a> {
         }
     }
     fn make_token(&self, t: TokenType) -> Token<'a> {
-        Token::new(t, &self.source[self.start..self.current], self.line)
+        Token::new(t, &self.source[self.start..self.current], self.line, self.start)
     }
-    fn error_token(&self, message: &'a str) -> Token<'a> {
-        Token::new(TokenError, message, self.line)
+    fn error_token(&self, error: ScanError) -> Token<'a> {
+        Token::error(error, self.line, self.start, self.current - self.start)
     }
     fn skip_whitespace(&mut self) {
         loop {
@@ -62,7 +62,7 @@ impl<'a> Scanner<'a> {
             return self.make_token(TokenEof);
         }
         match self.advance_unchecked() {
-            c if c.is_alphabetic() => return self.identifier(),
+            c if c.is_alphabetic() || c == '_' => return self.identifier(),
             c if c.is_numeric() => return self.number(),
             '"' => return self.string(),
             '(' => return self.make_token(TokenLeftParen),
@@ -83,12 +83,13 @@ impl<'a> Scanner<'a> {
 
             _ => {}
         };
-        Token::new(TokenType::TokenEof, &self.source, 1)
+        self.error_token(ScanError::UnexpectedCharacter)
     }
     fn advance_unchecked(&mut self) -> char {
-        self.current += 1;
-        let a = self.peekable.next();
-        a.expect("msg")
+        let a = self.peekable.next().expect("msg");
+        // current 是字节偏移, 多字节字符需要按 utf8 长度前进
+        self.current += a.len_utf8();
+        a
     }
     fn peek(&mut self) -> Option<char> {
         let a = self.peekable.peek(0);
@@ -111,18 +112,21 @@ impl<'a> Scanner<'a> {
     }
     fn if_match_token(&mut self, expected: char, match_expected: TokenType, not_match: TokenType) -> Token<'a> {
         let t_type = self.if_match(expected, match_expected, not_match);
-        return self.make_token(t_type);
+        self.make_token(t_type)
     }
     fn string(&mut self) -> Token<'a> {
         loop {
             if self.is_at_end() {
-                return self.error_token("Unterminated string");
+                return self.error_token(ScanError::UnterminatedString);
             }
             let peek = self.peek().unwrap();
             if peek == '"' {
                 self.advance_unchecked();
                 break;
             }
+            if peek == '\n' {
+                self.line += 1;
+            }
             self.advance_unchecked();
         }
         self.make_token(TokenString)
@@ -139,7 +143,7 @@ impl<'a> Scanner<'a> {
                 self.advance_unchecked();
                 continue;
             }
-            if peek == '.' && met_dot == false && self.peek_next().is_some_and(char::is_numeric) {
+            if peek == '.' && !met_dot && self.peek_next().is_some_and(char::is_numeric) {
                 met_dot = true;
                 self.advance_unchecked();
                 continue;
@@ -154,7 +158,7 @@ impl<'a> Scanner<'a> {
                 break;
             }
             let peek = self.peek().unwrap();
-            if peek.is_alphanumeric() {
+            if peek.is_alphanumeric() || peek == '_' {
                 self.advance_unchecked();
                 continue;
             }
@@ -167,3 +171,17 @@ impl<'a> Scanner<'a> {
         self.make_token(TokenIdentifier)
     }
 }
+
+/// 逐个列出 `source` 扫描出的 token: 行号, 类型, 词素
+pub fn dump_tokens(source: &str) -> String {
+    let mut scanner = Scanner::new(source);
+    let mut text = String::new();
+    loop {
+        let token = scanner.scan_token();
+        let _ = writeln!(text, "{:4} {:?} '{}'", token.line, token.t_type, token.start);
+        if token.t_type == TokenType::TokenEof {
+            break;
+        }
+    }
+    text
+}
@@ -5,7 +5,7 @@ use TokenType::*;
 
 type KType = LazyLock<HashMap<&'static str, TokenType>>;
 
-const KEYWORDS: KType = KType::new(|| {
+static KEYWORDS: KType = KType::new(|| {
     HashMap::from([
         ("and", TokenAnd),
         ("class", TokenClass),
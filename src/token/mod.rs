@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 mod token;
 mod token_type;
 mod keywords;
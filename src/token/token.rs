@@ -2,18 +2,53 @@ use crate::value::Value;
 
 use super::token_type::TokenType;
 use TokenType::*;
+
+/// 扫描错误的种类
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanError {
+    UnexpectedCharacter,
+    UnterminatedString,
+}
+impl ScanError {
+    pub fn message(self) -> &'static str {
+        match self {
+            ScanError::UnexpectedCharacter => "unexpected character",
+            ScanError::UnterminatedString => "Unterminated string",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Token<'a> {
     pub t_type: TokenType,
     pub start: &'a str,
     pub line: usize,
+    /// 在源码中的字节偏移
+    pub offset: usize,
+    /// 在源码中所占的字节数; 错误 token 的 `start` 是错误信息, 不能用它推算
+    pub len: usize,
+    /// 错误 token 的错误种类, 其他 token 为 `None`
+    pub error: Option<ScanError>,
 }
 impl<'a> Token<'a> {
-    pub fn new(t_type: TokenType, lexme: &'a str, line: usize) -> Self {
+    pub fn new(t_type: TokenType, lexme: &'a str, line: usize, offset: usize) -> Self {
         Self {
             t_type,
             start: lexme,
             line,
+            offset,
+            len: lexme.len(),
+            error: None,
+        }
+    }
+    pub fn error(error: ScanError, line: usize, offset: usize, len: usize) -> Self {
+        Self {
+            t_type: TokenError,
+            start: error.message(),
+            line,
+            offset,
+            len,
+            error: Some(error),
         }
     }
     pub fn is(&self, t_type: TokenType) -> bool {
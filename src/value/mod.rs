@@ -1,9 +1,13 @@
+use crate::object::ObjRef;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Value {
     Number(f64),
     Bool(bool),
     Nil,
+    Obj(ObjRef),
 }
+#[allow(clippy::result_unit_err)]
 impl Value {
     pub fn as_number(&self) -> Result<f64,()> {
         match self {
@@ -17,4 +21,20 @@ impl Value {
             _ => Err(()),
         }
     }
+    pub fn as_obj(&self) -> Result<ObjRef,()> {
+        match self {
+            Value::Obj(r) => Ok(*r),
+            _ => Err(()),
+        }
+    }
+}
+/// Lox 的相等语义: 不同类型的值永不相等, 字符串已驻留, 比较句柄即可
+pub fn values_equal(a: Value, b: Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Nil, Value::Nil) => true,
+        (Value::Obj(a), Value::Obj(b)) => a == b,
+        _ => false,
+    }
 }
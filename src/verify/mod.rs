@@ -0,0 +1,401 @@
+//! 字节码校验: 在执行来自外部的字节码之前检查它不会让虚拟机越界或崩溃
+//!
+//! 逐条解码检查指令和操作数是否合法, 再沿所有控制流路径推算栈深度,
+//! 确认不会弹空栈, 被闭包捕获的局部变量在关闭之前不会出栈, 各路径汇合处栈深度一致,
+//! 并且每条路径都以 `OPRETURN` 结束。值的类型仍由虚拟机在运行时检查
+use std::{collections::BTreeSet, fmt::Display};
+
+use crate::{
+    chunk::OpCode,
+    object::{Heap, ObjFunction, ObjRef},
+    value::Value,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyErrorKind {
+    InvalidOpCode(u8),
+    /// 指令的操作数超出了代码末尾
+    TruncatedInstruction,
+    ConstantOutOfRange(usize),
+    /// 用作名字的常量不是字符串
+    ExpectedString(usize),
+    /// `OPCLOSURE` 引用的常量不是函数
+    ExpectedFunction(usize),
+    /// `OPCLOSURE` 给出的捕获个数与函数原型记录的不一致
+    UpvalueCountMismatch { expected: usize, found: usize },
+    /// 捕获描述中 is_local 只能是 0 或 1
+    InvalidCapture(u8),
+    LocalOutOfRange(usize),
+    UpvalueOutOfRange(usize),
+    /// 跳转目标越界或落在某条指令的中间
+    InvalidJumpTarget,
+    StackUnderflow,
+    /// 两条控制流路径汇合时栈深度不同
+    StackMismatch { expected: usize, found: usize },
+    /// 执行到代码末尾仍未返回
+    FallsOffEnd,
+    /// 被捕获的局部变量没有经过 `OPCLOSE_UPVALUE` 就出栈, upvalue 会指向栈外
+    CapturedLocalPopped(usize),
+    /// 顶层代码以不带参数, 不捕获变量的闭包执行
+    InvalidScript { arity: usize, upvalue_count: usize },
+}
+impl Display for VerifyErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyErrorKind::InvalidOpCode(byte) => write!(f, "unknown opcode {}", byte),
+            VerifyErrorKind::TruncatedInstruction => write!(f, "instruction runs past the end of the code"),
+            VerifyErrorKind::ConstantOutOfRange(idx) => write!(f, "constant {} out of range", idx),
+            VerifyErrorKind::ExpectedString(idx) => write!(f, "constant {} is not a string", idx),
+            VerifyErrorKind::ExpectedFunction(idx) => write!(f, "constant {} is not a function", idx),
+            VerifyErrorKind::UpvalueCountMismatch { expected, found } => {
+                write!(f, "closure captures {} upvalues but the function has {}", found, expected)
+            }
+            VerifyErrorKind::InvalidCapture(byte) => write!(f, "invalid capture kind {}", byte),
+            VerifyErrorKind::LocalOutOfRange(slot) => write!(f, "local slot {} out of range", slot),
+            VerifyErrorKind::UpvalueOutOfRange(slot) => write!(f, "upvalue {} out of range", slot),
+            VerifyErrorKind::InvalidJumpTarget => write!(f, "jump target is not an instruction"),
+            VerifyErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            VerifyErrorKind::StackMismatch { expected, found } => {
+                write!(f, "stack depth {} does not match {} on another path", found, expected)
+            }
+            VerifyErrorKind::FallsOffEnd => write!(f, "execution falls off the end of the code"),
+            VerifyErrorKind::CapturedLocalPopped(slot) => {
+                write!(f, "local slot {} is popped while an upvalue still refers to it", slot)
+            }
+            VerifyErrorKind::InvalidScript { arity, upvalue_count } => write!(
+                f,
+                "top-level code can't have parameters or upvalues (has {} and {})",
+                arity, upvalue_count
+            ),
+        }
+    }
+}
+
+/// 校验失败的位置和原因
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    /// 出错的函数名, 顶层代码为 "script"
+    pub function: String,
+    pub offset: usize,
+    pub kind: VerifyErrorKind,
+}
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid bytecode in {} at {:04}: {}", self.function, self.offset, self.kind)
+    }
+}
+
+/// 校验作为顶层代码执行的函数 `function` 及其常量表中嵌套的所有函数
+pub fn verify_function(heap: &Heap, function: ObjRef) -> Result<(), VerifyError> {
+    let f = heap.as_function(function).expect("not a function");
+    if f.arity != 0 || f.upvalue_count != 0 {
+        return Err(VerifyError {
+            function: heap.function_name(f),
+            offset: 0,
+            kind: VerifyErrorKind::InvalidScript {
+                arity: f.arity,
+                upvalue_count: f.upvalue_count,
+            },
+        });
+    }
+    verify_prototype(heap, function)
+}
+
+fn verify_prototype(heap: &Heap, function: ObjRef) -> Result<(), VerifyError> {
+    let f = heap.as_function(function).expect("not a function");
+    Verifier::new(heap, f).verify()?;
+    for constant in &f.chunk.constants {
+        if let Value::Obj(r) = *constant {
+            if heap.as_function(r).is_some() {
+                verify_prototype(heap, r)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 某条指令执行前的栈状态
+#[derive(Clone)]
+struct State {
+    depth: usize,
+    /// 可能被打开的 upvalue 引用着的局部变量槽位
+    captured: BTreeSet<usize>,
+}
+
+struct Verifier<'a> {
+    heap: &'a Heap,
+    function: &'a ObjFunction,
+    code: &'a [u8],
+    /// 每个指令起点处解码出的指令及其长度, 操作数所在的字节为 `None`
+    instructions: Vec<Option<(OpCode, usize)>>,
+}
+impl<'a> Verifier<'a> {
+    fn new(heap: &'a Heap, function: &'a ObjFunction) -> Self {
+        Self {
+            heap,
+            function,
+            code: &function.chunk.code,
+            instructions: vec![],
+        }
+    }
+    fn error(&self, offset: usize, kind: VerifyErrorKind) -> VerifyError {
+        VerifyError {
+            function: self.heap.function_name(self.function),
+            offset,
+            kind,
+        }
+    }
+    fn verify(mut self) -> Result<(), VerifyError> {
+        self.instructions = vec![None; self.code.len()];
+        let mut offset = 0;
+        while offset < self.code.len() {
+            let instruction = self.decode(offset)?;
+            self.instructions[offset] = Some(instruction);
+            offset += instruction.1;
+        }
+        self.check_stack()
+    }
+    fn byte(&self, offset: usize) -> Result<u8, VerifyError> {
+        self.code.get(offset).copied().ok_or(self.error(offset, VerifyErrorKind::TruncatedInstruction))
+    }
+    fn short(&self, offset: usize) -> Result<usize, VerifyError> {
+        Ok((self.byte(offset)? as usize) << 8 | self.byte(offset + 1)? as usize)
+    }
+    fn constant(&self, at: usize, idx: usize) -> Result<Value, VerifyError> {
+        self.function
+            .chunk
+            .constants
+            .get(idx)
+            .copied()
+            .ok_or(self.error(at, VerifyErrorKind::ConstantOutOfRange(idx)))
+    }
+    /// 常量下标占用的字节数
+    fn index_width(op: OpCode) -> usize {
+        if op.is_long() {
+            3
+        } else {
+            1
+        }
+    }
+    /// 读取 `at` 处指令的常量下标
+    fn index(&self, op: OpCode, at: usize) -> Result<usize, VerifyError> {
+        if op.is_long() {
+            Ok((self.byte(at + 1)? as usize) << 16 | self.short(at + 2)?)
+        } else {
+            Ok(self.byte(at + 1)? as usize)
+        }
+    }
+    /// 检查 `at` 处指令的常量下标指向字符串
+    fn name(&self, op: OpCode, at: usize) -> Result<(), VerifyError> {
+        let idx = self.index(op, at)?;
+        match self.constant(at, idx)? {
+            Value::Obj(r) if self.heap.as_string(r).is_some() => Ok(()),
+            _ => Err(self.error(at, VerifyErrorKind::ExpectedString(idx))),
+        }
+    }
+    fn upvalue(&self, at: usize, slot: usize) -> Result<(), VerifyError> {
+        if slot >= self.function.upvalue_count {
+            return Err(self.error(at, VerifyErrorKind::UpvalueOutOfRange(slot)));
+        }
+        Ok(())
+    }
+    /// 解码 `offset` 处的指令, 检查与栈深度无关的操作数, 返回指令和它占用的字节数
+    fn decode(&self, offset: usize) -> Result<(OpCode, usize), VerifyError> {
+        use OpCode::*;
+        let byte = self.code[offset];
+        let op = OpCode::try_from(byte).map_err(|_| self.error(offset, VerifyErrorKind::InvalidOpCode(byte)))?;
+        // 常量下标之后的偏移
+        let next = offset + 1 + Self::index_width(op);
+        let len = match op.short_form() {
+            OPCONSTANT => {
+                self.constant(offset, self.index(op, offset)?)?;
+                next - offset
+            }
+            OPDEFINE_GLOBAL | OPGET_GLOBAL | OPSET_GLOBAL | OPCLASS | OPGET_PROPERTY | OPSET_PROPERTY
+            | OPMETHOD | OPGET_SUPER => {
+                self.name(op, offset)?;
+                next - offset
+            }
+            OPINVOKE | OPSUPER_INVOKE => {
+                self.name(op, offset)?;
+                self.byte(next)?;
+                next + 1 - offset
+            }
+            OPGET_UPVALUE | OPSET_UPVALUE => {
+                self.upvalue(offset, self.byte(offset + 1)? as usize)?;
+                2
+            }
+            OPGET_LOCAL | OPSET_LOCAL | OPCALL => {
+                self.byte(offset + 1)?;
+                2
+            }
+            OPJUMP | OPJUMP_IF_FALSE | OPLOOP => {
+                self.short(offset + 1)?;
+                3
+            }
+            OPCLOSURE => {
+                let idx = self.index(op, offset)?;
+                let function = match self.constant(offset, idx)? {
+                    Value::Obj(r) => self.heap.as_function(r),
+                    _ => None,
+                };
+                let Some(function) = function else {
+                    return Err(self.error(offset, VerifyErrorKind::ExpectedFunction(idx)));
+                };
+                let count = self.short(next)?;
+                if count != function.upvalue_count {
+                    let kind = VerifyErrorKind::UpvalueCountMismatch {
+                        expected: function.upvalue_count,
+                        found: count,
+                    };
+                    return Err(self.error(offset, kind));
+                }
+                for i in 0..count {
+                    let is_local = self.byte(next + 2 + 2 * i)?;
+                    let index = self.byte(next + 3 + 2 * i)? as usize;
+                    match is_local {
+                        // 捕获局部变量的槽位要结合栈深度检查
+                        1 => {}
+                        0 => self.upvalue(offset, index)?,
+                        _ => return Err(self.error(offset, VerifyErrorKind::InvalidCapture(is_local))),
+                    }
+                }
+                next + 2 + 2 * count - offset
+            }
+            OPNIL | OPTRUE | OPFALSE | OPRETURN | OPEQUAL | OPGREATER | OPLESS | OPADD | OPSUBTRACT
+            | OPMULTIPLY | OPDIVIDE | OPNOT | OPNEGATE | OPPRINT | OPPOP | OPCLOSE_UPVALUE | OPINHERIT => 1,
+            long => unreachable!("{:?} after short_form", long),
+        };
+        Ok((op, len))
+    }
+    /// 指令在栈深度为 `depth` 时弹出和压入的值的个数; 局部变量槽位在这里检查
+    fn stack_effect(&self, offset: usize, op: OpCode, depth: usize) -> Result<(usize, usize), VerifyError> {
+        use OpCode::*;
+        let operand = |i: usize| self.code[offset + i] as usize;
+        // 常量下标之后的操作数相对指令起点的位置
+        let next = 1 + Self::index_width(op);
+        let local = |slot: usize, limit: usize| {
+            if slot < limit {
+                Ok(())
+            } else {
+                Err(self.error(offset, VerifyErrorKind::LocalOutOfRange(slot)))
+            }
+        };
+        let effect = match op.short_form() {
+            OPCONSTANT | OPNIL | OPTRUE | OPFALSE | OPGET_GLOBAL | OPGET_UPVALUE | OPCLASS => (0, 1),
+            OPGET_LOCAL => {
+                local(operand(1), depth)?;
+                (0, 1)
+            }
+            OPSET_LOCAL => {
+                local(operand(1), depth)?;
+                (1, 1)
+            }
+            OPCLOSURE => {
+                for i in 0..operand(next) << 8 | operand(next + 1) {
+                    if operand(next + 2 + 2 * i) == 1 {
+                        // 递归的局部函数会捕获闭包自己将要占用的槽位
+                        local(operand(next + 3 + 2 * i), depth + 1)?;
+                    }
+                }
+                (0, 1)
+            }
+            OPEQUAL | OPGREATER | OPLESS | OPADD | OPSUBTRACT | OPMULTIPLY | OPDIVIDE => (2, 1),
+            OPNOT | OPNEGATE | OPSET_GLOBAL | OPSET_UPVALUE | OPGET_PROPERTY | OPJUMP_IF_FALSE => (1, 1),
+            OPRETURN | OPPRINT | OPPOP | OPDEFINE_GLOBAL | OPCLOSE_UPVALUE => (1, 0),
+            OPSET_PROPERTY | OPMETHOD | OPINHERIT | OPGET_SUPER => (2, 1),
+            OPCALL => (operand(1) + 1, 1),
+            OPINVOKE => (operand(next) + 1, 1),
+            // 父类在接收者和参数之上
+            OPSUPER_INVOKE => (operand(next) + 2, 1),
+            OPJUMP | OPLOOP => (0, 0),
+            long => unreachable!("{:?} after short_form", long),
+        };
+        Ok(effect)
+    }
+    /// `OPCLOSURE` 捕获的局部变量槽位
+    fn captured_locals(&self, offset: usize, op: OpCode) -> Vec<usize> {
+        let next = offset + 1 + Self::index_width(op);
+        let count = (self.code[next] as usize) << 8 | self.code[next + 1] as usize;
+        (0..count)
+            .filter(|i| self.code[next + 2 + 2 * i] == 1)
+            .map(|i| self.code[next + 3 + 2 * i] as usize)
+            .collect()
+    }
+    /// 沿控制流推算每条指令执行前的栈深度; 进入函数时栈上有被调用者和参数
+    fn check_stack(&self) -> Result<(), VerifyError> {
+        if self.code.is_empty() {
+            return Err(self.error(0, VerifyErrorKind::FallsOffEnd));
+        }
+        let mut states: Vec<Option<State>> = vec![None; self.code.len()];
+        states[0] = Some(State {
+            depth: self.function.arity + 1,
+            captured: BTreeSet::new(),
+        });
+        let mut work = vec![0];
+        while let Some(offset) = work.pop() {
+            let State { depth, mut captured } = states[offset].clone().expect("queued instruction without state");
+            let (op, len) = self.instructions[offset].expect("queued offset is not an instruction");
+            let (pops, pushes) = self.stack_effect(offset, op, depth)?;
+            if depth < pops {
+                return Err(self.error(offset, VerifyErrorKind::StackUnderflow));
+            }
+            let after = depth - pops + pushes;
+
+            match op.short_form() {
+                OpCode::OPRETURN => {}
+                // 先关闭栈顶槽位上的 upvalue 再出栈
+                OpCode::OPCLOSE_UPVALUE => captured.retain(|slot| *slot < depth - 1),
+                _ => {
+                    if let Some(&slot) = captured.range(depth - pops..).next() {
+                        return Err(self.error(offset, VerifyErrorKind::CapturedLocalPopped(slot)));
+                    }
+                    if op.short_form() == OpCode::OPCLOSURE {
+                        captured.extend(self.captured_locals(offset, op));
+                    }
+                }
+            }
+
+            let next = offset + len;
+            let jump = || self.short(offset + 1);
+            let successors = match op {
+                OpCode::OPRETURN => vec![],
+                OpCode::OPJUMP => vec![(next.checked_add(jump()?), true)],
+                OpCode::OPJUMP_IF_FALSE => vec![(Some(next), false), (next.checked_add(jump()?), true)],
+                OpCode::OPLOOP => vec![(next.checked_sub(jump()?), true)],
+                _ => vec![(Some(next), false)],
+            };
+            for (target, is_jump) in successors {
+                let target = match target {
+                    Some(target) if target < self.code.len() && self.instructions[target].is_some() => target,
+                    _ if is_jump => return Err(self.error(offset, VerifyErrorKind::InvalidJumpTarget)),
+                    _ => return Err(self.error(offset, VerifyErrorKind::FallsOffEnd)),
+                };
+                match &mut states[target] {
+                    None => {
+                        states[target] = Some(State {
+                            depth: after,
+                            captured: captured.clone(),
+                        });
+                        work.push(target);
+                    }
+                    Some(state) if state.depth != after => {
+                        let kind = VerifyErrorKind::StackMismatch {
+                            expected: state.depth,
+                            found: after,
+                        };
+                        return Err(self.error(target, kind));
+                    }
+                    // 汇合处取各路径被捕获槽位的并集, 集合变大时重新检查后续指令
+                    Some(state) => {
+                        if !captured.is_subset(&state.captured) {
+                            state.captured.extend(captured.iter().copied());
+                            work.push(target);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
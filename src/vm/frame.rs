@@ -0,0 +1,11 @@
+use crate::object::ObjRef;
+
+/// 一次函数调用的活动记录
+pub struct CallFrame {
+    pub closure: ObjRef,
+    /// 闭包对应的函数原型, 缓存下来以免每次取指令都要多查一次堆
+    pub function: ObjRef,
+    pub ip: usize,
+    /// 该帧的槽位 0 在 VM 栈中的下标
+    pub slots: usize,
+}
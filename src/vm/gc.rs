@@ -0,0 +1,72 @@
+use crate::{
+    object::{Obj, ObjRef},
+    value::Value,
+};
+
+use super::VM;
+
+impl VM {
+    /// 以 VM 的根集合以及调用方额外给出的根进行一轮完整回收
+    pub(crate) fn collect_with_roots(&mut self, extra_roots: &[Value]) {
+        if self.heap.log_gc {
+            eprintln!("-- gc begin");
+        }
+        for i in 0..self.stack.len() {
+            self.heap.mark_value(self.stack[i]);
+        }
+        for i in 0..self.frames.len() {
+            self.heap.mark_object(self.frames[i].closure);
+        }
+        for i in 0..self.open_upvalues.len() {
+            self.heap.mark_object(self.open_upvalues[i]);
+        }
+        for (&name, &value) in self.globals.iter() {
+            self.heap.mark_object(name);
+            self.heap.mark_value(value);
+        }
+        for i in 0..self.native_roots.len() {
+            self.heap.mark_value(self.native_roots[i]);
+        }
+        self.scripts.retain(|script| script.strong_count() > 0);
+        for script in self.scripts.iter().filter_map(|script| script.upgrade()) {
+            self.heap.mark_object(*script);
+        }
+        self.heap.mark_object(self.init_string);
+        for &root in extra_roots {
+            self.heap.mark_value(root);
+        }
+        self.heap.collect();
+    }
+    /// 堆超过阈值 (或开启了 `stress_gc`) 时回收; 每次分配前调用
+    pub(crate) fn maybe_collect(&mut self, extra_roots: &[Value]) {
+        if self.heap.should_collect() {
+            self.collect_with_roots(extra_roots);
+        }
+    }
+    pub(crate) fn alloc(&mut self, obj: Obj) -> ObjRef {
+        self.maybe_collect(&[]);
+        self.heap.alloc(obj)
+    }
+    pub(crate) fn take_string(&mut self, chars: String) -> ObjRef {
+        self.maybe_collect(&[]);
+        self.heap.take_string(chars)
+    }
+    /// 立即进行一轮回收
+    pub fn collect_garbage(&mut self) {
+        self.collect_with_roots(&[]);
+    }
+    /// 每次分配前都进行回收
+    pub fn set_stress_gc(&mut self, stress: bool) {
+        self.heap.stress_gc = stress;
+    }
+    /// 在标准错误输出上报告分配、释放和每轮回收的统计
+    pub fn set_log_gc(&mut self, log: bool) {
+        self.heap.log_gc = log;
+    }
+    pub fn bytes_allocated(&self) -> usize {
+        self.heap.bytes_allocated()
+    }
+    pub fn live_objects(&self) -> usize {
+        self.heap.live_objects()
+    }
+}
@@ -1,26 +1,114 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    rc::Weak,
+};
+
+mod frame;
+mod gc;
+mod native;
+mod script;
+
 use crate::{
-    chunk::{debug, Chunk, OpCode},
+    chunk::{debug::Disassembler, Chunk, OpCode},
     interpreter::InterpretErr,
-    value::Value,
+    loxc::{read_loxc, write_loxc, LoxcError},
+    object::{Heap, Obj, ObjBoundMethod, ObjClass, ObjClosure, ObjInstance, ObjNative, ObjRef, ObjUpvalue},
+    value::{values_equal, Value},
+    verify::{verify_function, VerifyError},
+    Compiler,
 };
+use frame::CallFrame;
+pub use native::{Arity, NativeFn};
+pub use script::Script;
 use InterpretErr::*;
-pub struct VM<'a> {
-    chunk: &'a Chunk,
+
+/// 调用栈的最大深度
+const FRAMES_MAX: usize = 64;
+
+pub struct VM {
+    frames: Vec<CallFrame>,
     stack: Vec<Value>,
-    ip: usize,
+    pub(crate) heap: Heap,
+    globals: HashMap<ObjRef, Value>,
+    /// 仍指向栈上变量的 upvalue, 按栈槽位升序排列
+    open_upvalues: Vec<ObjRef>,
+    /// 宿主通过 `intern` 创建的值, 宿主函数返回后 (或下一次从顶层开始执行时) 才解除
+    native_roots: Vec<Value>,
+    /// 交给宿主的顶层函数; 句柄被丢弃后在下一轮回收时移除
+    scripts: Vec<Weak<ObjRef>>,
+    /// 驻留的 "init", 构造实例时据此查找初始化方法
+    init_string: ObjRef,
+    out: Box<dyn Write>,
+    /// 最近一次运行时错误发生时栈上的值, 已格式化, 因此不会让对象免于回收
+    error_stack: Vec<String>,
+    /// 自创建以来执行过的指令条数
+    instructions: u64,
+    /// 执行每条指令前在标准错误输出上打印栈和指令
+    trace: bool,
+    /// 每个函数编译完成后在标准错误输出上打印它的字节码
+    pub(crate) dump_bytecode: bool,
 }
-impl<'a> VM<'a> {
+impl VM {
     fn reset_stack(&mut self) {
         self.stack.clear();
+        self.frames.clear();
+        self.open_upvalues.clear();
+    }
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("no call frame")
+    }
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("no call frame")
+    }
+    fn chunk(&self) -> &Chunk {
+        let function = self.frame().function;
+        &self.heap.as_function(function).expect("frame is not a function").chunk
     }
-    fn read_byte(&mut self) -> OpCode {
-        let byte = self.chunk.code[self.ip];
-        self.ip += 1;
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frames.last_mut().expect("no call frame");
+        let function = self.heap.as_function(frame.function).expect("frame is not a function");
+        let byte = function.chunk.code[frame.ip];
+        frame.ip += 1;
         byte
     }
-    fn read_const(&mut self) -> Value {
-        let idx = self.read_byte().as_value_idx();
-        self.chunk.constants[idx]
+    fn read_short(&mut self) -> usize {
+        let high = self.read_byte() as usize;
+        high << 8 | self.read_byte() as usize
+    }
+    /// 读取指令 `op` 的常量下标: `_LONG` 指令为三字节, 其余为一个字节
+    fn read_const(&mut self, op: OpCode) -> Value {
+        let idx = if op.is_long() {
+            let high = self.read_byte() as usize;
+            high << 16 | self.read_short()
+        } else {
+            self.read_byte() as usize
+        };
+        self.chunk().constants[idx]
+    }
+    fn read_string(&mut self, op: OpCode) -> ObjRef {
+        self.read_const(op).as_obj().expect("constant is not a string")
+    }
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+    fn undefined_variable(&mut self, name: ObjRef) -> InterpretErr {
+        let name = self.heap.format_value(Value::Obj(name));
+        self.runtime_error(&format!("Undefined variable '{}'", name))
+    }
+    fn undefined_property(&mut self, name: ObjRef) -> InterpretErr {
+        let name = self.heap.format_value(Value::Obj(name));
+        self.runtime_error(&format!("Undefined property '{}'", name))
+    }
+    fn as_instance(&self, value: Value) -> Option<&ObjInstance> {
+        value.as_obj().ok().and_then(|r| self.heap.as_instance(r))
+    }
+    /// 编译器生成的字节码中这些位置总是类, 但外部加载的字节码只校验了结构, 类型要在运行时检查
+    fn as_class(&self, value: Value) -> Option<ObjRef> {
+        value.as_obj().ok().filter(|r| self.heap.as_class(*r).is_some())
+    }
+    fn is_closure(&self, value: Value) -> bool {
+        value.as_obj().is_ok_and(|r| self.heap.as_closure(r).is_some())
     }
     fn push_value(&mut self, value: Value) {
         self.stack.push(value);
@@ -28,21 +116,212 @@ impl<'a> VM<'a> {
     fn pop_value(&mut self) -> Value {
         self.stack.pop().unwrap()
     }
+    /// 生成运行时错误: 按调用栈从内到外附上每一帧所在的源码行, 并清空栈以便 VM 可以继续复用
+    fn runtime_error(&mut self, message: &str) -> InterpretErr {
+        let mut trace = message.to_string();
+        for frame in self.frames.iter().rev() {
+            let function = self.heap.as_function(frame.function).expect("frame is not a function");
+            // ip 已经越过了出错的指令
+            let line = function.chunk.line_at(frame.ip.saturating_sub(1));
+            let location = match function.name {
+                Some(_) => format!("{}()", self.heap.function_name(function)),
+                None => "script".to_string(),
+            };
+            trace += &format!("\n[line {}] in {}", line, location);
+        }
+        self.error_stack = self.stack.iter().map(|value| self.heap.format_value(*value)).collect();
+        self.reset_stack();
+        RuntimeError(trace)
+    }
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<(), InterpretErr> {
+        if let Value::Obj(r) = callee {
+            match self.heap.get(r) {
+                Obj::Closure(_) => return self.call(r, arg_count),
+                Obj::Native(native) => {
+                    let (arity, function) = (native.arity, native.function);
+                    return self.call_native(arity, function, arg_count);
+                }
+                Obj::Class(_) => {
+                    // 类本身还在栈上, 分配期间不会被回收; 随后被新实例替换, 作为 `init` 的 `this`
+                    let instance = self.alloc(Obj::Instance(ObjInstance::new(r)));
+                    let slot = self.stack.len() - 1 - arg_count;
+                    self.stack[slot] = Value::Obj(instance);
+                    let init = self.heap.as_class(r).expect("not a class").methods.get(&self.init_string).copied();
+                    if let Some(init) = init {
+                        return self.call(init.as_obj().expect("method is not a closure"), arg_count);
+                    }
+                    if arg_count != 0 {
+                        let message = format!("Expected 0 arguments but got {}", arg_count);
+                        return Err(self.runtime_error(&message));
+                    }
+                    return Ok(());
+                }
+                Obj::BoundMethod(bound) => {
+                    let (receiver, method) = (bound.receiver, bound.method);
+                    let slot = self.stack.len() - 1 - arg_count;
+                    self.stack[slot] = receiver;
+                    return self.call(method, arg_count);
+                }
+                _ => {}
+            }
+        }
+        Err(self.runtime_error("Can only call functions and classes."))
+    }
+    fn call_native(&mut self, arity: Arity, function: NativeFn, arg_count: usize) -> Result<(), InterpretErr> {
+        if let Arity::Fixed(expected) = arity {
+            if !arity.accepts(arg_count) {
+                let message = format!("Expected {} arguments but got {}", expected, arg_count);
+                return Err(self.runtime_error(&message));
+            }
+        }
+        let args_start = self.stack.len() - arg_count;
+        let args = self.stack[args_start..].to_vec();
+        let roots = self.native_roots.len();
+        let result = function(self, &args);
+        self.native_roots.truncate(roots);
+        let result = result.map_err(|message| self.runtime_error(&message))?;
+        // 弹出参数和被调用的函数本身
+        self.stack.truncate(args_start - 1);
+        self.push_value(result);
+        Ok(())
+    }
+    /// `receiver.name(args)`: 字段优先, 否则直接调用类中的方法
+    fn invoke(&mut self, name: ObjRef, arg_count: usize) -> Result<(), InterpretErr> {
+        let receiver = self.peek(arg_count);
+        let Some(instance) = self.as_instance(receiver) else {
+            return Err(self.runtime_error("Only instances have methods."));
+        };
+        if let Some(field) = instance.fields.get(&name).copied() {
+            let slot = self.stack.len() - 1 - arg_count;
+            self.stack[slot] = field;
+            return self.call_value(field, arg_count);
+        }
+        let class = instance.class;
+        self.invoke_from_class(class, name, arg_count)
+    }
+    fn invoke_from_class(&mut self, class: ObjRef, name: ObjRef, arg_count: usize) -> Result<(), InterpretErr> {
+        let class = self.heap.as_class(class).expect("not a class");
+        let Some(method) = class.methods.get(&name).copied() else {
+            return Err(self.undefined_property(name));
+        };
+        self.call(method.as_obj().expect("method is not a closure"), arg_count)
+    }
+    /// 把类中名为 `name` 的方法与栈顶的实例绑定, 替换栈顶
+    fn bind_method(&mut self, class: ObjRef, name: ObjRef) -> Result<(), InterpretErr> {
+        let class = self.heap.as_class(class).expect("not a class");
+        let Some(method) = class.methods.get(&name).copied() else {
+            return Err(self.undefined_property(name));
+        };
+        let method = method.as_obj().expect("method is not a closure");
+        // 接收者仍在栈顶, 分配期间不会被回收
+        let bound = self.alloc(Obj::BoundMethod(ObjBoundMethod::new(self.peek(0), method)));
+        self.pop_value();
+        self.push_value(Value::Obj(bound));
+        Ok(())
+    }
+    fn call(&mut self, closure: ObjRef, arg_count: usize) -> Result<(), InterpretErr> {
+        let function = self.heap.as_closure(closure).expect("callee is not a closure").function;
+        let arity = self.heap.as_function(function).expect("closure without function").arity;
+        if arg_count != arity {
+            let message = format!("Expected {} arguments but got {}", arity, arg_count);
+            return Err(self.runtime_error(&message));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.runtime_error("Stack overflow."));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            function,
+            ip: 0,
+            slots: self.stack.len() - arg_count - 1,
+        });
+        Ok(())
+    }
+    fn upvalue(&self, slot: usize) -> ObjRef {
+        let closure = self.heap.as_closure(self.frame().closure).expect("frame is not a closure");
+        closure.upvalues[slot]
+    }
+    /// 返回捕获栈槽位 `location` 的 upvalue, 同一个变量只会创建一个 upvalue
+    fn capture_upvalue(&mut self, location: usize) -> ObjRef {
+        let heap = &self.heap;
+        let position = self.open_upvalues.binary_search_by_key(&location, |r| {
+            match heap.as_upvalue(*r) {
+                Some(ObjUpvalue::Open(slot)) => *slot,
+                _ => unreachable!("closed upvalue in open list"),
+            }
+        });
+        match position {
+            Ok(i) => self.open_upvalues[i],
+            Err(i) => {
+                let upvalue = self.alloc(Obj::Upvalue(ObjUpvalue::Open(location)));
+                self.open_upvalues.insert(i, upvalue);
+                upvalue
+            }
+        }
+    }
+    /// 把栈槽位不低于 `last` 的 upvalue 关闭, 变量的值搬到 upvalue 自身
+    fn close_upvalues(&mut self, last: usize) {
+        while let Some(&upvalue) = self.open_upvalues.last() {
+            let upvalue = self.heap.as_upvalue_mut(upvalue).expect("not an upvalue");
+            let ObjUpvalue::Open(slot) = *upvalue else {
+                unreachable!("closed upvalue in open list");
+            };
+            if slot < last {
+                break;
+            }
+            *upvalue = ObjUpvalue::Closed(self.stack[slot]);
+            self.open_upvalues.pop();
+        }
+    }
+    fn is_string(&self, value: Value) -> bool {
+        value.as_obj().is_ok_and(|r| self.heap.as_string(r).is_some())
+    }
+    fn concatenate(&mut self) -> Result<(), InterpretErr> {
+        let b = self.pop_value().as_obj().expect("operand is not a string");
+        let a = self.pop_value().as_obj().expect("operand is not a string");
+        let (Some(a), Some(b)) = (self.heap.as_string(a), self.heap.as_string(b)) else {
+            unreachable!("operands are checked before concatenation");
+        };
+        let chars = format!("{}{}", a.chars, b.chars);
+        let result = self.take_string(chars);
+        self.push_value(Value::Obj(result));
+        Ok(())
+    }
     fn binary_op(&mut self, op: OpCode) -> Result<(), InterpretErr> {
-        let b = self.pop_value().as_number().map_err(|_| RuntimeError)?;
-        let a = self.pop_value().as_number().map_err(|_| RuntimeError)?;
+        if op == OpCode::OPADD && self.is_string(self.peek(0)) && self.is_string(self.peek(1)) {
+            return self.concatenate();
+        }
+        let (Value::Number(a), Value::Number(b)) = (self.peek(1), self.peek(0)) else {
+            let message = match op {
+                OpCode::OPADD => "Operands must be two numbers or two strings.",
+                _ => "Operands must be numbers.",
+            };
+            return Err(self.runtime_error(message));
+        };
+        self.pop_value();
+        self.pop_value();
 
         match op {
-            OpCode::OPEQUAL => self.push_value(Value::Bool(a == b)),
             OpCode::OPGREATER => self.push_value(Value::Bool(a > b)),
             OpCode::OPLESS => self.push_value(Value::Bool(a < b)),
             OpCode::OPADD => self.push_value(Value::Number(a + b)),
             OpCode::OPSUBTRACT => self.push_value(Value::Number(a - b)),
             OpCode::OPMULTIPLY => self.push_value(Value::Number(a * b)),
             OpCode::OPDIVIDE => self.push_value(Value::Number(a / b)),
-            _ => return Err(RuntimeError),
+            _ => unreachable!("{:?} is not a binary operator", op),
         };
-        return Ok(());
+        Ok(())
+    }
+    fn trace_instruction(&self) {
+        let mut stack = String::new();
+        for value in &self.stack {
+            stack += &format!("[ {} ]", self.heap.format_value(*value));
+        }
+        let format_value = |value| self.heap.format_value(value);
+        let mut disassembler = Disassembler::new(self.chunk(), &format_value);
+        disassembler.instruction(self.frame().ip);
+        eprintln!("          {}", stack);
+        eprint!("{}", disassembler.take());
     }
     fn is_false(&self, value: Value) -> bool {
         match value {
@@ -52,52 +331,414 @@ impl<'a> VM<'a> {
         }
     }
 }
-impl<'a> VM<'a> {
-    pub fn new(chunk: &'a Chunk) -> Self {
-        Self {
-            chunk,
+impl VM {
+    pub fn new() -> Self {
+        let mut heap = Heap::new();
+        let init_string = heap.intern("init");
+        let mut vm = Self {
+            frames: vec![],
             stack: vec![],
-            ip: 0,
+            heap,
+            globals: HashMap::new(),
+            open_upvalues: vec![],
+            native_roots: vec![],
+            scripts: vec![],
+            init_string,
+            out: Box::new(io::stdout()),
+            error_stack: vec![],
+            instructions: 0,
+            trace: false,
+            dump_bytecode: false,
+        };
+        native::define_builtins(&mut vm);
+        vm
+    }
+    /// 注册一个宿主函数, 脚本中以全局变量 `name` 访问
+    pub fn define_native(&mut self, name: &str, arity: Arity, function: NativeFn) {
+        // `intern` 返回的名字在分配宿主函数对象期间仍是根
+        let name = self.intern(name).as_obj().expect("interned name is not an object");
+        let native = self.alloc(Obj::Native(ObjNative {
+            name,
+            arity,
+            function,
+        }));
+        self.globals.insert(name, Value::Obj(native));
+    }
+    /// 返回驻留后的字符串值, 供宿主函数构造返回值; 规则见 [`NativeFn`]
+    pub fn intern(&mut self, chars: &str) -> Value {
+        self.maybe_collect(&[]);
+        let value = Value::Obj(self.heap.intern(chars));
+        self.native_roots.push(value);
+        value
+    }
+    /// 按 `print` 的格式把值转换成字符串
+    pub fn format_value(&self, value: Value) -> String {
+        self.heap.format_value(value)
+    }
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+    pub fn set_dump_bytecode(&mut self, dump: bool) {
+        self.dump_bytecode = dump;
+    }
+    /// 替换 `print` 语句的输出目标, 默认为标准输出
+    pub fn set_output(&mut self, out: impl Write + 'static) {
+        self.out = Box::new(out);
+    }
+    /// 把顶层函数交给宿主前登记为回收的根
+    fn script(&mut self, function: ObjRef) -> Script {
+        let (script, pin) = Script::new(function);
+        self.scripts.push(pin);
+        script
+    }
+    fn compile_function(&mut self, source: &str) -> Result<ObjRef, InterpretErr> {
+        // 编译期的字符串常量与运行时创建的字符串共用同一张驻留表
+        let function = Compiler::new(source, self).compile().map_err(CompileError)?;
+        Ok(self.debug_verify(function))
+    }
+    fn compile_repl(&mut self, source: &str) -> Result<ObjRef, InterpretErr> {
+        let function = Compiler::new_repl(source, self).compile().map_err(CompileError)?;
+        Ok(self.debug_verify(function))
+    }
+    /// 编译器的输出只在调试构建中校验, 出错说明编译器有缺陷
+    fn debug_verify(&self, function: ObjRef) -> ObjRef {
+        if cfg!(debug_assertions) {
+            if let Err(e) = verify_function(&self.heap, function) {
+                panic!("compiler emitted {}", e);
+            }
+        }
+        function
+    }
+    /// 只编译不执行, 返回顶层函数
+    pub fn compile(&mut self, source: &str) -> Result<Script, InterpretErr> {
+        let function = self.compile_function(source)?;
+        Ok(self.script(function))
+    }
+    pub fn interpret(&mut self, source: &str) -> Result<(), InterpretErr> {
+        let function = self.compile_function(source)?;
+        self.run_closure(function)
+    }
+    /// 以 REPL 模式执行一段输入: 全局变量在多次调用之间保留, 顶层表达式的结果会被打印
+    pub fn interpret_repl(&mut self, source: &str) -> Result<(), InterpretErr> {
+        let function = self.compile_repl(source)?;
+        self.run_closure(function)
+    }
+    /// 以 REPL 模式编译但不执行, 返回反汇编
+    pub fn disassemble_repl(&mut self, source: &str) -> Result<String, InterpretErr> {
+        let function = self.compile_repl(source)?;
+        Ok(self.disassemble_object(function))
+    }
+    /// 编译但不执行, 返回顶层代码及其中嵌套的所有函数的反汇编
+    pub fn disassemble(&mut self, source: &str) -> Result<String, InterpretErr> {
+        let function = self.compile_function(source)?;
+        Ok(self.disassemble_object(function))
+    }
+    /// 编译 `source`, 返回 `.loxc` 文件的内容
+    pub fn compile_to_loxc(&mut self, source: &str) -> Result<Vec<u8>, InterpretErr> {
+        let function = self.compile_function(source)?;
+        Ok(write_loxc(&self.heap, function))
+    }
+    pub fn disassemble_function(&self, script: &Script) -> String {
+        self.disassemble_object(script.function())
+    }
+    fn disassemble_object(&self, function: ObjRef) -> String {
+        let f = self.heap.as_function(function).expect("not a function");
+        let format_value = |value| self.heap.format_value(value);
+        let mut text = Disassembler::new(&f.chunk, &format_value).chunk(&self.heap.function_name(f));
+        for constant in &f.chunk.constants {
+            if let Value::Obj(r) = *constant {
+                if self.heap.as_function(r).is_some() {
+                    text += &self.disassemble_object(r);
+                }
+            }
+        }
+        text
+    }
+    /// 当前的值栈, 自栈底向上
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+    /// 最近一次执行出错时栈上的值, 自栈底向上; 执行成功时为空
+    pub fn error_stack(&self) -> &[String] {
+        &self.error_stack
+    }
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions
+    }
+    /// 加载 `.loxc` 文件内容, 返回顶层函数
+    pub fn load_loxc(&mut self, bytes: &[u8]) -> Result<Script, LoxcError> {
+        let function = read_loxc(&mut self.heap, bytes)?;
+        Ok(self.script(function))
+    }
+    /// 校验函数及其嵌套函数的字节码; 加载得到的函数总是已经校验过, 编译得到的函数只在调试构建中校验过
+    pub fn verify(&self, script: &Script) -> Result<(), VerifyError> {
+        verify_function(&self.heap, script.function())
+    }
+    /// 执行一个已经编译或加载好的顶层函数
+    pub fn run_function(&mut self, script: &Script) -> Result<(), InterpretErr> {
+        self.run_closure(script.function())
+    }
+    fn run_closure(&mut self, function: ObjRef) -> Result<(), InterpretErr> {
+        // 宿主函数中嵌套执行时, 外层宿主函数的值仍要保留
+        if self.frames.is_empty() {
+            self.native_roots.clear();
         }
+        self.error_stack.clear();
+        // 分配闭包时函数对象还不可达, 先压栈, 再替换成闭包
+        self.push_value(Value::Obj(function));
+        let closure = self.alloc(Obj::Closure(ObjClosure::new(function)));
+        self.pop_value();
+        self.push_value(Value::Obj(closure));
+        self.call(closure, 0)?;
+        self.run()
     }
     pub fn run(&mut self) -> Result<(), InterpretErr> {
         loop {
-            if cfg!(debug_assertions) {
-                println!("       {:?}", self.stack);
-
-                debug::disassemble_instruction(self.chunk, self.ip);
+            if self.trace {
+                self.trace_instruction();
             }
 
+            self.instructions += 1;
             let byte = self.read_byte();
-            match byte {
-                OpCode::OPCONSTANT => {
-                    let v = self.read_const();
+            let Ok(op) = OpCode::try_from(byte) else {
+                return Err(self.runtime_error(&format!("Unknown opcode {}.", byte)));
+            };
+            match op {
+                OpCode::OPCONSTANT | OpCode::OPCONSTANT_LONG => {
+                    let v = self.read_const(op);
                     self.push_value(v)
                 }
                 OpCode::OPNIL => self.push_value(Value::Nil),
                 OpCode::OPTRUE => self.push_value(Value::Bool(true)),
                 OpCode::OPFALSE => self.push_value(Value::Bool(false)),
                 OpCode::OPRETURN => {
+                    let result = self.pop_value();
+                    let frame = self.frames.pop().expect("no call frame");
+                    self.close_upvalues(frame.slots);
+                    // 丢弃被调用函数及其参数和局部变量
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        break;
+                    }
+                    self.push_value(result);
+                }
+                OpCode::OPCALL => {
+                    let arg_count = self.read_byte() as usize;
+                    self.call_value(self.peek(arg_count), arg_count)?;
+                }
+                OpCode::OPPRINT => {
+                    let value = self.pop_value();
+                    let text = self.heap.format_value(value);
+                    if writeln!(self.out, "{}", text).is_err() {
+                        return Err(self.runtime_error("Failed to write output."));
+                    }
+                }
+                OpCode::OPPOP => {
+                    self.pop_value();
+                }
+                OpCode::OPGET_LOCAL => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.push_value(self.stack[slot]);
+                }
+                OpCode::OPSET_LOCAL => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0);
+                }
+                OpCode::OPCLOSURE | OpCode::OPCLOSURE_LONG => {
+                    let function = self.read_const(op).as_obj().expect("constant is not a function");
+                    let count = self.read_short();
+                    let mut closure = ObjClosure::new(function);
+                    for _ in 0..count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        let upvalue = if is_local {
+                            self.capture_upvalue(self.frame().slots + index)
+                        } else {
+                            self.upvalue(index)
+                        };
+                        closure.upvalues.push(upvalue);
+                    }
+                    let closure = self.alloc(Obj::Closure(closure));
+                    self.push_value(Value::Obj(closure));
+                }
+                OpCode::OPGET_UPVALUE => {
+                    let slot = self.read_byte() as usize;
+                    let upvalue = self.upvalue(slot);
+                    let value = match *self.heap.as_upvalue(upvalue).expect("not an upvalue") {
+                        ObjUpvalue::Open(location) => self.stack[location],
+                        ObjUpvalue::Closed(value) => value,
+                    };
+                    self.push_value(value);
+                }
+                OpCode::OPSET_UPVALUE => {
+                    let slot = self.read_byte() as usize;
+                    let upvalue = self.upvalue(slot);
+                    let value = self.peek(0);
+                    match self.heap.as_upvalue_mut(upvalue).expect("not an upvalue") {
+                        ObjUpvalue::Open(location) => self.stack[*location] = value,
+                        closed => *closed = ObjUpvalue::Closed(value),
+                    }
+                }
+                OpCode::OPCLOSE_UPVALUE => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop_value();
+                }
+                OpCode::OPCLASS | OpCode::OPCLASS_LONG => {
+                    let name = self.read_string(op);
+                    let class = self.alloc(Obj::Class(ObjClass::new(name)));
+                    self.push_value(Value::Obj(class));
+                }
+                OpCode::OPGET_PROPERTY | OpCode::OPGET_PROPERTY_LONG => {
+                    let name = self.read_string(op);
+                    let Some(instance) = self.as_instance(self.peek(0)) else {
+                        return Err(self.runtime_error("Only instances have properties."));
+                    };
+                    // 字段会遮蔽同名方法
+                    if let Some(value) = instance.fields.get(&name).copied() {
+                        self.pop_value();
+                        self.push_value(value);
+                        continue;
+                    }
+                    let class = instance.class;
+                    self.bind_method(class, name)?;
+                }
+                OpCode::OPSET_PROPERTY | OpCode::OPSET_PROPERTY_LONG => {
+                    let name = self.read_string(op);
+                    let value = self.peek(0);
+                    let target = self.peek(1).as_obj().ok();
+                    let Some(instance) = target.and_then(|r| self.heap.as_instance_mut(r)) else {
+                        return Err(self.runtime_error("Only instances have fields."));
+                    };
+                    if instance.fields.insert(name, value).is_none() {
+                        self.heap.resize(target.expect("checked above"));
+                    }
+                    // 弹出实例, 赋值的值留在栈顶
+                    self.pop_value();
+                    self.pop_value();
+                    self.push_value(value);
+                }
+                OpCode::OPMETHOD | OpCode::OPMETHOD_LONG => {
+                    let name = self.read_string(op);
+                    let method = self.peek(0);
+                    let Some(class) = self.as_class(self.peek(1)) else {
+                        return Err(self.runtime_error("Methods can only be added to classes."));
+                    };
+                    // 方法表中只存放闭包, 调用方法时依赖这一点
+                    if !self.is_closure(method) {
+                        return Err(self.runtime_error("Method must be a function."));
+                    }
+                    match self.heap.get_mut(class) {
+                        Obj::Class(class) => class.methods.insert(name, method),
+                        _ => unreachable!("checked to be a class"),
+                    };
+                    self.heap.resize(class);
+                    self.pop_value();
+                }
+                OpCode::OPINHERIT => {
+                    let superclass = self.peek(1).as_obj().ok().and_then(|r| self.heap.as_class(r));
+                    let Some(superclass) = superclass else {
+                        return Err(self.runtime_error("Superclass must be a class."));
+                    };
+                    // 继承时一次性把父类方法复制到子类, 之后子类声明的同名方法会覆盖它们
+                    let methods = superclass.methods.clone();
+                    let Some(subclass) = self.as_class(self.peek(0)) else {
+                        return Err(self.runtime_error("Subclass must be a class."));
+                    };
+                    match self.heap.get_mut(subclass) {
+                        Obj::Class(subclass) => subclass.methods.extend(methods),
+                        _ => unreachable!("checked to be a class"),
+                    }
+                    self.heap.resize(subclass);
+                    self.pop_value();
+                }
+                OpCode::OPGET_SUPER | OpCode::OPGET_SUPER_LONG => {
+                    let name = self.read_string(op);
+                    let superclass = self.pop_value();
+                    let Some(superclass) = self.as_class(superclass) else {
+                        return Err(self.runtime_error("Superclass must be a class."));
+                    };
+                    self.bind_method(superclass, name)?;
+                }
+                OpCode::OPSUPER_INVOKE | OpCode::OPSUPER_INVOKE_LONG => {
+                    let name = self.read_string(op);
+                    let arg_count = self.read_byte() as usize;
+                    let superclass = self.pop_value();
+                    let Some(superclass) = self.as_class(superclass) else {
+                        return Err(self.runtime_error("Superclass must be a class."));
+                    };
+                    self.invoke_from_class(superclass, name, arg_count)?;
+                }
+                OpCode::OPINVOKE | OpCode::OPINVOKE_LONG => {
+                    let name = self.read_string(op);
+                    let arg_count = self.read_byte() as usize;
+                    self.invoke(name, arg_count)?;
+                }
+                OpCode::OPJUMP => {
+                    let offset = self.read_short();
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::OPJUMP_IF_FALSE => {
+                    let offset = self.read_short();
+                    if self.is_false(self.peek(0)) {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::OPLOOP => {
+                    let offset = self.read_short();
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::OPDEFINE_GLOBAL | OpCode::OPDEFINE_GLOBAL_LONG => {
+                    let name = self.read_string(op);
                     let value = self.pop_value();
-                    println!("return {:?}", value);
-                    break;
+                    self.globals.insert(name, value);
+                }
+                OpCode::OPGET_GLOBAL | OpCode::OPGET_GLOBAL_LONG => {
+                    let name = self.read_string(op);
+                    let Some(value) = self.globals.get(&name).copied() else {
+                        return Err(self.undefined_variable(name));
+                    };
+                    self.push_value(value);
+                }
+                OpCode::OPSET_GLOBAL | OpCode::OPSET_GLOBAL_LONG => {
+                    let name = self.read_string(op);
+                    // 赋值是表达式, 值留在栈顶
+                    let value = self.peek(0);
+                    let Some(slot) = self.globals.get_mut(&name) else {
+                        return Err(self.undefined_variable(name));
+                    };
+                    *slot = value;
+                }
+                OpCode::OPNEGATE => {
+                    let Value::Number(n) = self.peek(0) else {
+                        return Err(self.runtime_error("Operand must be a number."));
+                    };
+                    self.pop_value();
+                    self.push_value(Value::Number(-n));
                 }
                 OpCode::OPNOT => {
                     let value = self.pop_value();
                     self.push_value(Value::Bool(self.is_false(value)));
                 }
-                OpCode::OPEQUAL
-                | OpCode::OPGREATER
+                OpCode::OPEQUAL => {
+                    let b = self.pop_value();
+                    let a = self.pop_value();
+                    self.push_value(Value::Bool(values_equal(a, b)));
+                }
+                OpCode::OPGREATER
                 | OpCode::OPLESS
                 | OpCode::OPMULTIPLY
                 | OpCode::OPDIVIDE
                 | OpCode::OPADD
                 | OpCode::OPSUBTRACT => {
-                    self.binary_op(byte)?;
+                    self.binary_op(op)?;
                 }
-                _ => return Err(RuntimeError),
             }
         }
         Ok(())
     }
 }
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
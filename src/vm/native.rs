@@ -0,0 +1,46 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::value::Value;
+
+use super::VM;
+
+/// 宿主函数: 出错时返回错误信息, 由 VM 转换成带调用栈的运行时错误
+///
+/// 任何分配都可能触发回收。参数在 VM 栈上, 通过 [`VM::intern`] 创建的值在宿主函数返回前也一直是根,
+/// 因此可以放心地先后创建多个值; 不要把值保存到宿主函数返回之后
+pub type NativeFn = fn(&mut VM, &[Value]) -> Result<Value, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Fixed(usize),
+    /// 接受任意个数的参数
+    Variadic,
+}
+impl Arity {
+    pub fn accepts(&self, arg_count: usize) -> bool {
+        match self {
+            Arity::Fixed(n) => *n == arg_count,
+            Arity::Variadic => true,
+        }
+    }
+}
+
+/// 注册内置的宿主函数
+pub fn define_builtins(vm: &mut VM) {
+    vm.define_native("clock", Arity::Fixed(0), clock);
+    vm.define_native("str", Arity::Fixed(1), str);
+}
+
+/// 自 Unix 纪元以来的秒数
+fn clock(_vm: &mut VM, _args: &[Value]) -> Result<Value, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?;
+    Ok(Value::Number(now.as_secs_f64()))
+}
+
+/// 按 `print` 的格式把任意值转换成字符串
+fn str(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = vm.format_value(args[0]);
+    Ok(vm.intern(&text))
+}
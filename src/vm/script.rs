@@ -0,0 +1,19 @@
+use std::rc::{Rc, Weak};
+
+use crate::object::ObjRef;
+
+/// 编译或加载得到的顶层函数的句柄; 句柄 (及其克隆) 存在期间函数对象不会被回收
+#[derive(Debug, Clone)]
+pub struct Script {
+    function: Rc<ObjRef>,
+}
+impl Script {
+    pub(super) fn new(function: ObjRef) -> (Self, Weak<ObjRef>) {
+        let function = Rc::new(function);
+        let pin = Rc::downgrade(&function);
+        (Script { function }, pin)
+    }
+    pub(super) fn function(&self) -> ObjRef {
+        *self.function
+    }
+}
@@ -1,4 +1,7 @@
-use lox_vm_rust::{Scanner, Token, TokenType};
+#![allow(dead_code)]
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use lox_vm_rust::{Diagnostic, InterpretErr, Scanner, TokenType, VM};
 
 pub fn assert_number(scanner: &mut Scanner, value: f64) {
     let token = scanner.scan_token();
@@ -19,3 +22,47 @@ pub fn assert_identifier(scanner: &mut Scanner, value: &str) {
     assert_eq!(token.t_type, TokenType::TokenIdentifier);
     assert_eq!(token.start, value);
 }
+
+/// 可共享的输出缓冲区, 用于收集 `print` 的输出
+#[derive(Clone, Default)]
+pub struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+impl SharedBuf {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+pub fn run(source: &str) -> (Result<(), InterpretErr>, String) {
+    let buf = SharedBuf::default();
+    let mut vm = VM::new();
+    vm.set_output(buf.clone());
+    let result = vm.interpret(source);
+    (result, buf.contents())
+}
+pub fn assert_output(source: &str, expected: &str) {
+    let (result, output) = run(source);
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(output, expected);
+}
+/// 编译 `source`, 返回唯一的一条诊断; 没有出错, 出现运行时错误或诊断不止一条时测试失败
+pub fn compile_error(source: &str) -> Diagnostic {
+    match run(source).0 {
+        Err(InterpretErr::CompileError(mut diagnostics)) if diagnostics.len() == 1 => diagnostics.remove(0),
+        other => panic!("expected a single compile error, got {:?}", other),
+    }
+}
+/// 运行 `source`, 返回运行时错误信息; 没有出错或出现编译错误时测试失败
+pub fn runtime_error(source: &str) -> String {
+    match run(source).0 {
+        Err(InterpretErr::RuntimeError(message)) => message,
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
@@ -0,0 +1,103 @@
+mod common;
+use common::assert_output;
+use lox_vm_rust::VM;
+
+#[test]
+fn test_long_constants() {
+    // 超过 256 个字面量后改用 OPCONSTANT_LONG
+    let mut source = String::from("var total = 0;\n");
+    for i in 1..=400 {
+        source += &format!("total = total + {};\n", i);
+    }
+    source += "print total;\n";
+    assert_output(&source, "80200\n");
+}
+
+#[test]
+fn test_long_constants_in_function() {
+    let mut body = String::from("var v = 0;\n");
+    for i in 0..300 {
+        body += &format!("v = {}.5;\n", i);
+    }
+    let source = format!("fun f() {{\n{}return v;\n}}\nprint f();\n", body);
+    assert_output(&source, "299.5\n");
+}
+
+#[test]
+fn test_repeated_names_share_a_constant() {
+    let mut source = String::from("var x = \"s\";\n");
+    for _ in 0..300 {
+        source += "x = x;\n";
+    }
+    source += "print x;\n";
+    assert_output(&source, "s\n");
+}
+
+#[test]
+fn test_many_globals() {
+    let mut source = String::new();
+    for i in 0..130 {
+        source += &format!("var g{} = {};\n", i, i);
+    }
+    source += "print g129;\n";
+    assert_output(&source, "129\n");
+}
+
+#[test]
+fn test_long_name_operands() {
+    // 前 300 个名字占满单字节下标, 之后的名字和闭包改用 `_LONG` 指令
+    let mut source = String::new();
+    for i in 0..300 {
+        source += &format!("var g{} = nil;\n", i);
+    }
+    source += r#"
+class Point {
+  init(x) { this.x = x; }
+  get() { return this.x; }
+}
+class Point3 < Point {
+  get() { return super.get() + 1; }
+}
+fun make(x) { return Point3(x); }
+var p = make(1);
+p.x = p.x + 1;
+print p.get();
+print p.x;
+"#;
+    assert_output(&source, "3\n2\n");
+
+    let text = VM::new().disassemble(&source).unwrap();
+    for op in ["OPDEFINE_GLOBAL_LONG", "OPCLASS_LONG", "OPCLOSURE_LONG", "OPMETHOD_LONG", "OPINVOKE_LONG"] {
+        assert!(text.contains(op), "{} not emitted", op);
+    }
+    // 写入文件再加载, 校验器同样接受 `_LONG` 指令
+    let bytes = VM::new().compile_to_loxc(&source).unwrap();
+    let mut vm = VM::new();
+    assert!(vm.load_loxc(&bytes).is_ok());
+}
+
+#[test]
+fn test_literal_opcodes_and_shared_constants() {
+    let text = VM::new().disassemble("print nil; print true; print false; print 1; print 1; print \"a\" + \"a\";").unwrap();
+    assert!(text.contains("OPNIL\n"));
+    assert!(text.contains("OPTRUE\n"));
+    assert!(text.contains("OPFALSE\n"));
+    // 重复的数字和字符串只占一个常量, 下标始终放得进一个字节
+    let text = VM::new().disassemble(&"1.5; \"s\"; true;\n".repeat(300)).unwrap();
+    assert!(!text.contains("OPCONSTANT_LONG"));
+}
+
+#[test]
+fn test_long_jump() {
+    // 跳转距离超过一个字节
+    let mut source = String::from("var n = 0;\nif (n == 0) {\n");
+    for _ in 0..200 {
+        source += "n = n + 1;\n";
+    }
+    source += "}\nwhile (n > 0) {\n";
+    for _ in 0..100 {
+        source += "n = n - 1;\n";
+    }
+    source += "}\nprint n;\n";
+    assert_output(&source, "0\n");
+}
@@ -0,0 +1,166 @@
+mod common;
+use common::{assert_output, run, runtime_error};
+use lox_vm_rust::InterpretErr;
+
+#[test]
+fn test_class_and_instance() {
+    let source = r#"
+class Point {}
+print Point;
+var p = Point();
+print p;
+"#;
+    assert_output(source, "Point\nPoint instance\n");
+}
+
+#[test]
+fn test_fields() {
+    let source = r#"
+class Pair {}
+var pair = Pair();
+pair.first = 1;
+pair.second = 2;
+print pair.first + pair.second;
+pair.first = pair.second = "x";
+print pair.first;
+"#;
+    assert_output(source, "3\nx\n");
+}
+
+#[test]
+fn test_local_class() {
+    let source = r#"
+{
+    class Box {}
+    var b = Box();
+    b.inner = Box();
+    b.inner.value = "nested";
+    print b.inner.value;
+}
+"#;
+    assert_output(source, "nested\n");
+}
+
+#[test]
+fn test_property_errors() {
+    assert_eq!(
+        runtime_error("var a = 1; print a.x;"),
+        "Only instances have properties.\n[line 1] in script"
+    );
+    assert_eq!(runtime_error("true.x = 1;"), "Only instances have fields.\n[line 1] in script");
+    assert_eq!(
+        runtime_error("class A {} print A().missing;"),
+        "Undefined property 'missing'\n[line 1] in script"
+    );
+    assert_eq!(
+        runtime_error("class A {} A(1);"),
+        "Expected 0 arguments but got 1\n[line 1] in script"
+    );
+    assert_eq!(runtime_error("\"s\"();"), "Can only call functions and classes.\n[line 1] in script");
+}
+
+#[test]
+fn test_methods_and_this() {
+    let source = r#"
+class Counter {
+    add(n) {
+        this.count = this.count + n;
+        return this;
+    }
+    show() { print this.count; }
+}
+var c = Counter();
+c.count = 0;
+c.add(1).add(2);
+c.show();
+"#;
+    assert_output(source, "3\n");
+}
+
+#[test]
+fn test_initializer() {
+    let source = r#"
+class Point {
+    init(x, y) {
+        this.x = x;
+        this.y = y;
+        return;
+    }
+}
+var p = Point(1, 2);
+print p.x + p.y;
+print p.init(3, 4) == p;
+print p.x;
+"#;
+    assert_output(source, "3\ntrue\n3\n");
+}
+
+#[test]
+fn test_bound_method() {
+    let source = r#"
+class Greeter {
+    init(name) { this.name = name; }
+    greet() { return "hi " + this.name; }
+}
+var g = Greeter("lox").greet;
+print g;
+print g();
+"#;
+    assert_output(source, "<fn greet>\nhi lox\n");
+}
+
+#[test]
+fn test_this_captured_by_closure() {
+    let source = r#"
+class Box {
+    init(v) { this.v = v; }
+    getter() {
+        fun get() { return this.v; }
+        return get;
+    }
+}
+print Box("inner").getter()();
+"#;
+    assert_output(source, "inner\n");
+}
+
+#[test]
+fn test_field_shadows_method() {
+    let source = r#"
+fun shout() { return "field"; }
+class A {
+    m() { return "method"; }
+}
+var a = A();
+print a.m();
+a.m = shout;
+print a.m();
+"#;
+    assert_output(source, "method\nfield\n");
+}
+
+#[test]
+fn test_method_errors() {
+    assert_eq!(
+        runtime_error("class A { init(a) {} } A();"),
+        "Expected 1 arguments but got 0\n[line 1] in script"
+    );
+    assert_eq!(
+        runtime_error("class A {} A().missing();"),
+        "Undefined property 'missing'\n[line 1] in script"
+    );
+    assert_eq!(runtime_error("var x = 1; x.m();"), "Only instances have methods.\n[line 1] in script");
+}
+
+#[test]
+fn test_this_and_init_compile_errors() {
+    let codes = |source: &str| match run(source).0 {
+        Err(InterpretErr::CompileError(diagnostics)) => {
+            diagnostics.iter().map(|d| d.code.as_str()).collect::<Vec<_>>()
+        }
+        other => panic!("expected compile error, got {:?}", other),
+    };
+    assert_eq!(codes("print this;"), ["E0011"]);
+    assert_eq!(codes("fun f() { return this; }"), ["E0011"]);
+    assert_eq!(codes("class A { init() { return 1; } }"), ["E0012"]);
+}
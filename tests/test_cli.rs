@@ -0,0 +1,121 @@
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+fn lox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lox-vm-rust"))
+        .args(args)
+        .output()
+        .expect("failed to start binary")
+}
+
+/// 在临时目录中写入一个脚本, 文件名带上测试名以免并行测试互相覆盖
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("lox_cli_{}.lox", name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_run() {
+    let path = script("run", "print 1 + 2;");
+    let path = path.to_str().unwrap();
+    for args in [vec![path], vec!["run", path]] {
+        let output = lox(&args);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(stdout(&output), "3\n");
+        // 没有 --trace/--dump-bytecode 时不输出调试信息
+        assert_eq!(stderr(&output), "");
+    }
+}
+
+#[test]
+fn test_exit_codes() {
+    let bad = script("compile_error", "print 1 +;");
+    let output = lox(&["run", bad.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).starts_with("error[E0002]"));
+
+    let runtime = script("runtime_error", "print nil - 1;");
+    let output = lox(&["run", runtime.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stderr(&output), "Operands must be numbers.\n[line 1] in script\n");
+
+    let output = lox(&["run", "/no/such/script.lox"]);
+    assert_eq!(output.status.code(), Some(74));
+}
+
+#[test]
+fn test_check() {
+    let ok = script("check_ok", "print \"never printed\";");
+    let output = lox(&["check", ok.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+
+    let bad = script("check_bad", "var = 1;\nprint (;");
+    let output = lox(&["check", bad.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stderr(&output).matches("error[").count(), 2);
+}
+
+#[test]
+fn test_disasm_and_tokens() {
+    let path = script("disasm", "fun f() { return 1; }\nprint f();");
+    let output = lox(&["disasm", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    let text = stdout(&output);
+    assert!(text.starts_with("== script ==\n0000    1 OPCLOSURE        <fn f>\n"));
+    assert!(text.contains("== f ==\n"));
+
+    let output = lox(&["tokens", path.to_str().unwrap()]);
+    assert!(stdout(&output).starts_with("   1 TokenFun 'fun'\n"));
+}
+
+#[test]
+fn test_trace_and_dump_bytecode() {
+    let path = script("trace", "print 1;");
+    let output = lox(&["--trace", "run", path.to_str().unwrap()]);
+    assert_eq!(stdout(&output), "1\n");
+    assert!(stderr(&output).contains("OPPRINT"));
+
+    let output = lox(&["run", "--dump-bytecode", path.to_str().unwrap()]);
+    assert_eq!(stdout(&output), "1\n");
+    assert!(stderr(&output).starts_with("== script ==\n"));
+}
+
+#[test]
+fn test_compile_writes_bytecode_file() {
+    let path = script("compile", "print 1;");
+    let output_path = std::env::temp_dir().join("lox_cli_compile_out.loxc");
+    let _ = fs::remove_file(&output_path);
+    let output = lox(&["compile", path.to_str().unwrap(), "-o", output_path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    let bytes = fs::read(&output_path).unwrap();
+    assert_eq!(&bytes[..4], b"LOXC");
+
+    // run 通过魔数识别字节码文件
+    let output = lox(&["run", output_path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "1\n");
+    let output = lox(&["disasm", output_path.to_str().unwrap()]);
+    assert!(stdout(&output).starts_with("== script ==\n"));
+}
+
+#[test]
+fn test_run_rejects_corrupted_bytecode() {
+    let path = std::env::temp_dir().join("lox_cli_corrupted.loxc");
+    fs::write(&path, b"LOXC\x01\x00\x00").unwrap();
+    let output = lox(&["run", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains("truncated file"));
+}
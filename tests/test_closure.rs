@@ -0,0 +1,101 @@
+mod common;
+use common::assert_output;
+
+#[test]
+fn test_counter() {
+    let source = r#"
+fun make_counter() {
+    var count = 0;
+    fun counter() {
+        count = count + 1;
+        return count;
+    }
+    return counter;
+}
+var a = make_counter();
+var b = make_counter();
+print a();
+print a();
+print b();
+"#;
+    assert_output(source, "1\n2\n1\n");
+}
+
+#[test]
+fn test_shared_capture() {
+    // 两个闭包捕获同一个变量, 关闭后仍然共享
+    let source = r#"
+var get;
+var set;
+fun make() {
+    var x = "before";
+    fun g() { return x; }
+    fun s(v) { x = v; }
+    get = g;
+    set = s;
+}
+make();
+set("after");
+print get();
+"#;
+    assert_output(source, "after\n");
+}
+
+#[test]
+fn test_capture_by_reference_while_open() {
+    let source = r#"
+{
+    var x = 1;
+    fun show() { print x; }
+    x = 2;
+    show();
+}
+"#;
+    assert_output(source, "2\n");
+}
+
+#[test]
+fn test_nested_upvalues() {
+    let source = r#"
+fun outer() {
+    var x = "outer";
+    fun middle() {
+        fun inner() { return x; }
+        return inner;
+    }
+    return middle;
+}
+print outer()()();
+"#;
+    assert_output(source, "outer\n");
+}
+
+#[test]
+fn test_close_upvalue_in_block() {
+    let source = r#"
+var f;
+{
+    var a = "block";
+    fun g() { return a; }
+    f = g;
+}
+print f();
+"#;
+    assert_output(source, "block\n");
+}
+
+#[test]
+fn test_loop_closures() {
+    let source = r#"
+var first;
+var second;
+for (var i = 0; i < 2; i = i + 1) {
+    var j = i;
+    fun f() { return j; }
+    if (first == nil) first = f; else second = f;
+}
+print first();
+print second();
+"#;
+    assert_output(source, "0\n1\n");
+}
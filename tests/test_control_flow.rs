@@ -0,0 +1,40 @@
+mod common;
+use common::{assert_output, compile_error, runtime_error};
+use lox_vm_rust::ErrorCode;
+
+#[test]
+fn test_if_else() {
+    assert_output("if (true) print 1; else print 2;", "1\n");
+    assert_output("if (false) print 1; else print 2;", "2\n");
+    assert_output("if (nil) print 1; print 3;", "3\n");
+    assert_output("var a = 0; if (a) { a = 1; } print a;", "1\n");
+}
+
+#[test]
+fn test_while() {
+    let source = "var i = 0; var go = true; while (go) { print i; i = i + 1; if (i == 3) go = false; }";
+    assert_output(source, "0\n1\n2\n");
+    assert_output("while (false) print 1; print 2;", "2\n");
+}
+
+#[test]
+fn test_for() {
+    assert_output("for (var go = true; go; go = false) print 1;", "1\n");
+    let source = "var i = 0; for (var go = true; go; i = i + 1) { print i; if (i == 2) go = false; }";
+    assert_output(source, "0\n1\n2\n");
+    let source = "var go = true; for (; go;) { print 1; go = false; }";
+    assert_output(source, "1\n");
+    // 初始化子句中的变量只在循环内可见
+    assert_eq!(
+        runtime_error("for (var i = 0; false;) {} print i;"),
+        "Undefined variable 'i'\n[line 1] in script"
+    );
+}
+
+#[test]
+fn test_too_much_code_to_jump_over() {
+    let body = "1;".repeat(22_000);
+    let d = compile_error(&format!("if (true) {{ {} }}", body));
+    assert_eq!(d.code, ErrorCode::JumpTooLarge);
+    assert_eq!(d.message, "too much code to jump over");
+}
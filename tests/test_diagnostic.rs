@@ -0,0 +1,91 @@
+mod common;
+use common::run;
+use lox_vm_rust::{render, Diagnostic, ErrorCode, InterpretErr, Severity};
+
+fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    match run(source).0 {
+        Err(InterpretErr::CompileError(diagnostics)) => diagnostics,
+        other => panic!("expected compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_diagnostic_location() {
+    let source = "var a;\nvar b;\na + b = 1;";
+    let d = &diagnostics(source)[0];
+    assert_eq!(d.severity, Severity::Error);
+    assert_eq!(d.code, ErrorCode::InvalidAssignmentTarget);
+    assert_eq!(d.message, "invalid assignment target");
+    assert_eq!(d.line, 3);
+    assert_eq!(d.column, 7);
+    assert_eq!(&source[d.span.clone()], "=");
+}
+
+#[test]
+fn test_lexical_diagnostic() {
+    let source = "print \"abc";
+    let d = &diagnostics(source)[0];
+    assert_eq!(d.code, ErrorCode::Lexical);
+    assert_eq!(d.message, "Unterminated string");
+    assert_eq!(d.column, 7);
+    assert_eq!(&source[d.span.clone()], "\"abc");
+
+    let d = &diagnostics("print 1 # 2;")[0];
+    assert_eq!(d.code, ErrorCode::Lexical);
+    assert_eq!(d.column, 9);
+}
+
+#[test]
+fn test_diagnostic_at_end() {
+    let source = "print 1";
+    let d = &diagnostics(source)[0];
+    assert_eq!(d.code, ErrorCode::Syntax);
+    assert_eq!(d.span, 7..7);
+    assert_eq!(d.column, 8);
+}
+
+#[test]
+fn test_render() {
+    let source = "var a;\nvar b;\na + b = 1;";
+    let d = &diagnostics(source)[0];
+    let expected = "\
+error[E0003]: invalid assignment target
+  --> line 3, column 7
+  |
+3 | a + b = 1;
+  |       ^
+";
+    assert_eq!(render(source, d), expected);
+    assert_eq!(d.to_string(), "3:7: error[E0003]: invalid assignment target");
+}
+
+#[test]
+fn test_reports_every_independent_error() {
+    let source = "\
+print 1 +;
+var = 2;
+var ok = 3;
+{ var x = 1; var x = 2; }
+if (ok) print ok ok;
+print ok;";
+    let ds = diagnostics(source);
+    let lines: Vec<usize> = ds.iter().map(|d| d.line).collect();
+    assert_eq!(lines, vec![1, 2, 4, 5]);
+    assert_eq!(ds[2].code, ErrorCode::Redeclaration);
+}
+
+#[test]
+fn test_one_error_per_statement() {
+    // 同一条语句里的连锁错误只报告第一个
+    let ds = diagnostics("print (1 + ;");
+    assert_eq!(ds.len(), 1);
+}
+
+#[test]
+fn test_multiline_token_points_at_its_start() {
+    // 未结束的字符串跨越多行, 诊断指向它开始的位置而不是文件末尾
+    let source = "var s = \"abc\ndef;\nprint 1;\n";
+    let d = &diagnostics(source)[0];
+    assert_eq!((d.line, d.column), (1, 9));
+    assert!(render(source, d).contains("1 | var s = \"abc\n  |         ^^^^\n"));
+}
@@ -0,0 +1,80 @@
+mod common;
+use common::{assert_output, run};
+use lox_vm_rust::{ErrorCode, InterpretErr};
+
+#[test]
+fn test_call_and_return() {
+    assert_output("fun add(a, b) { return a + b; } print add(1, 2);", "3\n");
+    assert_output("fun f() { print 1; } f(); print f;", "1\n<fn f>\n");
+}
+
+#[test]
+fn test_implicit_nil_return() {
+    assert_output("fun f() {} print f();", "nil\n");
+    assert_output("fun f() { return; } print f();", "nil\n");
+}
+
+#[test]
+fn test_recursion() {
+    let source = "fun fib(n) { if (n < 2) return n; return fib(n - 2) + fib(n - 1); } print fib(10);";
+    assert_output(source, "55\n");
+}
+
+#[test]
+fn test_locals_in_frames() {
+    let source = r#"
+fun outer(a) {
+    var b = a * 2;
+    fun inner(c) { return c + 1; }
+    return inner(b) + a;
+}
+{
+    var x = 10;
+    print outer(x);
+    print x;
+}
+"#;
+    assert_output(source, "31\n10\n");
+}
+
+#[test]
+fn test_arity_mismatch() {
+    let (r, _) = run("fun f(a, b) {} f(1);");
+    assert_eq!(
+        r,
+        Err(InterpretErr::RuntimeError(
+            "Expected 2 arguments but got 1\n[line 1] in script".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_stack_trace() {
+    let source = "fun a() { return nil + 1; }\nfun b() { a(); }\nb();";
+    let (r, _) = run(source);
+    let expected = "Operands must be two numbers or two strings.\n[line 1] in a()\n[line 2] in b()\n[line 3] in script";
+    assert_eq!(r, Err(InterpretErr::RuntimeError(expected.to_string())));
+}
+
+#[test]
+fn test_call_non_function() {
+    let (r, _) = run(r#""str"();"#);
+    assert!(matches!(r, Err(InterpretErr::RuntimeError(_))));
+}
+
+#[test]
+fn test_stack_overflow() {
+    let (r, _) = run("fun f() { f(); } f();");
+    match r {
+        Err(InterpretErr::RuntimeError(message)) => assert!(message.starts_with("Stack overflow.")),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn test_top_level_return() {
+    match run("return 1;").0 {
+        Err(InterpretErr::CompileError(ds)) => assert_eq!(ds[0].code, ErrorCode::ReturnOutsideFunction),
+        other => panic!("{:?}", other),
+    }
+}
@@ -0,0 +1,193 @@
+mod common;
+use common::SharedBuf;
+use lox_vm_rust::{ObjRef, Value, VM};
+
+fn run_stressed(source: &str) -> String {
+    let buf = SharedBuf::default();
+    let mut vm = VM::new();
+    vm.set_output(buf.clone());
+    vm.set_stress_gc(true);
+    vm.interpret(source).unwrap();
+    buf.contents()
+}
+
+#[test]
+fn test_stress_strings() {
+    let source = r#"
+        var a = "foo";
+        var b = a + "bar";
+        { var c = b + "baz"; print c; }
+        print a + b;
+        print str(1 + 2) + "!";
+    "#;
+    assert_eq!(run_stressed(source), "foobarbaz\nfoofoobar\n3!\n");
+}
+
+#[test]
+fn test_stress_closures() {
+    let source = r#"
+        fun counter(prefix) {
+            var n = 0;
+            fun next() { n = n + 1; return prefix + str(n); }
+            return next;
+        }
+        var c = counter("n");
+        c();
+        c();
+        print c();
+        fun outer() {
+            var x = "closed";
+            fun inner() { print x; }
+            return inner;
+        }
+        outer()();
+    "#;
+    assert_eq!(run_stressed(source), "n3\nclosed\n");
+}
+
+#[test]
+fn test_stress_nested_functions() {
+    let source = r#"
+        fun a() {
+            fun b() { return "deep" + "er"; }
+            return b() + " and " + "deeper";
+        }
+        print a();
+    "#;
+    assert_eq!(run_stressed(source), "deeper and deeper\n");
+}
+
+#[test]
+fn test_garbage_is_freed() {
+    let source = r#"
+        var s = "";
+        for (var i = 0; i < 200; i = i + 1) {
+            s = str(i) + "-tmp";
+        }
+        print s;
+    "#;
+    let buf = SharedBuf::default();
+    let mut vm = VM::new();
+    vm.set_output(buf.clone());
+    vm.interpret(source).unwrap();
+    let before = vm.live_objects();
+    let bytes_before = vm.bytes_allocated();
+    vm.collect_garbage();
+    assert_eq!(buf.contents(), "199-tmp\n");
+    assert!(vm.live_objects() < before);
+    assert!(vm.bytes_allocated() < bytes_before);
+    // 全局变量仍然可达, 回收后可以继续使用
+    let buf = SharedBuf::default();
+    vm.set_output(buf.clone());
+    vm.interpret("print s + \"!\";").unwrap();
+    assert_eq!(buf.contents(), "199-tmp!\n");
+}
+
+#[test]
+fn test_freed_slots_are_reused() {
+    let mut vm = VM::new();
+    vm.set_output(SharedBuf::default());
+    vm.interpret("for (var i = 0; i < 50; i = i + 1) { str(i); }").unwrap();
+    vm.collect_garbage();
+    let live = vm.live_objects();
+    vm.interpret("for (var i = 0; i < 50; i = i + 1) { str(i); }").unwrap();
+    vm.collect_garbage();
+    assert_eq!(vm.live_objects(), live);
+}
+
+#[test]
+fn test_stress_instances() {
+    let source = r#"
+        class Node {}
+        var head = Node();
+        head.value = "a" + "0";
+        head.next = Node();
+        head.next.value = "b" + "1";
+        print head.value + head.next.value;
+    "#;
+    assert_eq!(run_stressed(source), "a0b1\n");
+}
+
+#[test]
+fn test_stress_methods() {
+    let source = r#"
+        class Pair {
+            init(a, b) { this.a = a; this.b = b; }
+            join() { return this.a + this.b; }
+        }
+        var m = Pair("x" + "1", "y" + "2").join;
+        print m();
+        print Pair("p", "q").join();
+    "#;
+    assert_eq!(run_stressed(source), "x1y2\npq\n");
+}
+
+#[test]
+fn test_stress_inheritance() {
+    let source = r#"
+        class A { name() { return "a" + "!"; } }
+        class B < A { name() { return "b" + super.name(); } }
+        print B().name();
+    "#;
+    assert_eq!(run_stressed(source), "ba!\n");
+}
+
+#[test]
+fn test_growing_fields_are_counted() {
+    // 两段脚本分配的对象相同, 只是一个把值存进实例字段, 一个存进全局变量
+    let live_bytes = |assign: &str| {
+        let mut source = String::from("class A {} var a = A();\n");
+        for i in 0..50 {
+            source += &format!("{}f{} = {};\n", assign, i, i);
+        }
+        let mut vm = VM::new();
+        vm.interpret(&source).unwrap();
+        vm.collect_garbage();
+        vm.bytes_allocated()
+    };
+    let fields = live_bytes("a.");
+    let globals = live_bytes("var ");
+    assert_eq!(fields - globals, 50 * std::mem::size_of::<(ObjRef, Value)>());
+}
+
+#[test]
+fn test_loaded_script_survives_until_run() {
+    let bytes = VM::new().compile_to_loxc("print \"loaded\";").unwrap();
+    let buf = SharedBuf::default();
+    let mut vm = VM::new();
+    vm.set_output(buf.clone());
+    vm.set_stress_gc(true);
+    let script = vm.load_loxc(&bytes).unwrap();
+    // 加载和执行之间的分配都会触发回收
+    vm.compile("print \"other\" + \"!\";").unwrap();
+    vm.intern("garbage");
+    vm.run_function(&script).unwrap();
+    assert_eq!(buf.contents(), "loaded\n");
+}
+
+#[test]
+fn test_compiled_script_survives_until_run() {
+    let buf = SharedBuf::default();
+    let mut vm = VM::new();
+    vm.set_output(buf.clone());
+    vm.set_stress_gc(true);
+    let script = vm.compile("print \"compiled\";").unwrap();
+    vm.compile("print \"other\" + \"!\";").unwrap();
+    vm.load_loxc(&VM::new().compile_to_loxc("print 1;").unwrap()).unwrap();
+    vm.run_function(&script).unwrap();
+    vm.run_function(&script).unwrap();
+    assert_eq!(buf.contents(), "compiled\ncompiled\n");
+}
+
+#[test]
+fn test_dropped_script_is_freed() {
+    let mut vm = VM::new();
+    vm.collect_garbage();
+    let live = vm.live_objects();
+    let script = vm.compile("fun f() {}").unwrap();
+    vm.collect_garbage();
+    assert!(vm.live_objects() > live);
+    drop(script);
+    vm.collect_garbage();
+    assert_eq!(vm.live_objects(), live);
+}
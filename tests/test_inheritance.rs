@@ -0,0 +1,103 @@
+mod common;
+use common::{assert_output, run, runtime_error};
+use lox_vm_rust::InterpretErr;
+
+#[test]
+fn test_inherited_methods() {
+    let source = r#"
+class Animal {
+    init(name) { this.name = name; }
+    speak() { return this.name + " makes a sound"; }
+}
+class Dog < Animal {}
+print Dog("rex").speak();
+"#;
+    assert_output(source, "rex makes a sound\n");
+}
+
+#[test]
+fn test_override_and_super() {
+    let source = r#"
+class A {
+    method() { return "A"; }
+    describe() { return "I am " + this.method(); }
+}
+class B < A {
+    method() { return "B then " + super.method(); }
+}
+class C < B {
+    method() {
+        var m = super.method;
+        return "C then " + m();
+    }
+}
+print B().describe();
+print C().describe();
+"#;
+    assert_output(source, "I am B then A\nI am C then B then A\n");
+}
+
+#[test]
+fn test_super_initializer() {
+    let source = r#"
+class Base {
+    init(x) { this.x = x; }
+}
+class Derived < Base {
+    init(x, y) {
+        super.init(x);
+        this.y = y;
+    }
+}
+var d = Derived(1, 2);
+print d.x + d.y;
+"#;
+    assert_output(source, "3\n");
+}
+
+#[test]
+fn test_super_in_closure() {
+    let source = r#"
+class A { say() { return "from A"; } }
+class B < A {
+    getter() {
+        fun f() { return super.say(); }
+        return f;
+    }
+}
+print B().getter()();
+"#;
+    assert_output(source, "from A\n");
+}
+
+#[test]
+fn test_override_does_not_affect_superclass() {
+    let source = r#"
+class A { name() { return "A"; } }
+class B < A { name() { return "B"; } }
+print A().name();
+print B().name();
+"#;
+    assert_output(source, "A\nB\n");
+}
+
+#[test]
+fn test_superclass_must_be_class() {
+    assert_eq!(
+        runtime_error("var NotClass = 1; class A < NotClass {}"),
+        "Superclass must be a class.\n[line 1] in script"
+    );
+}
+
+#[test]
+fn test_super_compile_errors() {
+    let codes = |source: &str| match run(source).0 {
+        Err(InterpretErr::CompileError(diagnostics)) => {
+            diagnostics.iter().map(|d| d.code.as_str()).collect::<Vec<_>>()
+        }
+        other => panic!("expected compile error, got {:?}", other),
+    };
+    assert_eq!(codes("class A < A {}"), ["E0013"]);
+    assert_eq!(codes("print super.x;"), ["E0014"]);
+    assert_eq!(codes("class A { m() { return super.m(); } }"), ["E0014"]);
+}
@@ -0,0 +1,31 @@
+mod common;
+use common::assert_output;
+
+#[test]
+fn test_and() {
+    assert_output("print true and 1;", "1\n");
+    assert_output("print false and 1;", "false\n");
+    assert_output("print nil and 1;", "nil\n");
+    assert_output(r#"print 1 and "x" and 2;"#, "2\n");
+}
+
+#[test]
+fn test_or() {
+    assert_output(r#"print nil or "x";"#, "x\n");
+    assert_output("print 1 or 2;", "1\n");
+    assert_output("print false or nil;", "nil\n");
+}
+
+#[test]
+fn test_short_circuit() {
+    // 右操作数不会被求值, 因此未定义的变量不会触发运行时错误
+    assert_output("print false and undefined_name;", "false\n");
+    assert_output("print true or undefined_name;", "true\n");
+    assert_output("var a = 1; true or (a = 2); print a;", "1\n");
+}
+
+#[test]
+fn test_precedence() {
+    assert_output("print false and true or 3;", "3\n");
+    assert_output("print true or false and nil;", "true\n");
+}
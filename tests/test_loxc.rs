@@ -0,0 +1,123 @@
+mod common;
+use common::SharedBuf;
+use lox_vm_rust::{LoxcError, VM};
+
+const SOURCE: &str = r#"
+fun makeCounter() {
+  var count = 0;
+  fun counter() {
+    count = count + 1;
+    return count;
+  }
+  return counter;
+}
+class Greeter {
+  init(name) { this.name = name; }
+  greet() { return "hi " + this.name; }
+}
+var counter = makeCounter();
+counter();
+print counter();
+print Greeter("lox").greet();
+print 1.5 * 4;
+print !nil == true;
+"#;
+
+fn compile(source: &str) -> Vec<u8> {
+    VM::new().compile_to_loxc(source).unwrap()
+}
+
+fn load(bytes: &[u8]) -> Result<(), LoxcError> {
+    VM::new().load_loxc(bytes).map(|_| ())
+}
+
+#[test]
+fn test_round_trip() {
+    let bytes = compile(SOURCE);
+    // 在另一个虚拟机中加载, 确保不依赖编译时的堆
+    let mut vm = VM::new();
+    let buf = SharedBuf::default();
+    vm.set_output(buf.clone());
+    let script = vm.load_loxc(&bytes).unwrap();
+    vm.run_function(&script).unwrap();
+    assert_eq!(buf.contents(), "2\nhi lox\n6\ntrue\n");
+}
+
+#[test]
+fn test_round_trip_is_stable() {
+    let mut vm = VM::new();
+    let expected = vm.disassemble(SOURCE).unwrap();
+    let bytes = vm.compile_to_loxc(SOURCE).unwrap();
+    let mut loaded = VM::new();
+    let script = loaded.load_loxc(&bytes).unwrap();
+    assert_eq!(loaded.disassemble_function(&script), expected);
+}
+
+#[test]
+fn test_runtime_error_lines_survive() {
+    let bytes = compile("var a = 1;\n\nprint a - nil;");
+    let mut vm = VM::new();
+    let script = vm.load_loxc(&bytes).unwrap();
+    let err = vm.run_function(&script).unwrap_err();
+    assert_eq!(format!("{:?}", err), "RuntimeError(\"Operands must be numbers.\\n[line 3] in script\")");
+}
+
+#[test]
+fn test_bad_magic_and_version() {
+    assert_eq!(load(b""), Err(LoxcError::BadMagic));
+    assert_eq!(load(b"LOXD\x01\x00"), Err(LoxcError::BadMagic));
+
+    let mut bytes = compile("print 1;");
+    bytes[4] = 9;
+    assert_eq!(
+        load(&bytes),
+        Err(LoxcError::UnsupportedVersion { found: 9, expected: 1 })
+    );
+}
+
+#[test]
+fn test_every_truncation_is_rejected() {
+    let bytes = compile(SOURCE);
+    for len in 4..bytes.len() {
+        assert!(
+            matches!(load(&bytes[..len]), Err(LoxcError::Truncated { .. })),
+            "prefix of {} bytes",
+            len
+        );
+    }
+}
+
+#[test]
+fn test_trailing_bytes() {
+    let mut bytes = compile("print 1;");
+    let len = bytes.len();
+    bytes.push(0);
+    assert_eq!(load(&bytes), Err(LoxcError::TrailingBytes { offset: len }));
+}
+
+#[test]
+fn test_corrupted_constant_tag() {
+    let mut bytes = compile("print 1;");
+    // 唯一的常量是数字, 它的标签位于文件末尾 9 个字节处
+    let offset = bytes.len() - 9;
+    bytes[offset] = 7;
+    assert_eq!(load(&bytes), Err(LoxcError::InvalidConstantTag { tag: 7, offset }));
+}
+
+#[test]
+fn test_invalid_utf8_string() {
+    let mut bytes = compile("print \"a\";");
+    let offset = bytes.len() - 1;
+    bytes[offset] = 0xff;
+    assert_eq!(load(&bytes), Err(LoxcError::InvalidUtf8 { offset }));
+}
+
+#[test]
+fn test_error_messages() {
+    assert_eq!(LoxcError::BadMagic.to_string(), "not a loxc file (bad magic bytes)");
+    assert_eq!(
+        LoxcError::UnsupportedVersion { found: 2, expected: 1 }.to_string(),
+        "unsupported loxc version 2 (this build reads version 1)"
+    );
+    assert_eq!(LoxcError::Truncated { offset: 10 }.to_string(), "truncated file at byte 10");
+}
@@ -0,0 +1,83 @@
+mod common;
+use common::{assert_output, run, SharedBuf};
+use lox_vm_rust::{Arity, InterpretErr, Value, VM};
+
+fn sum(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let mut total = 0.0;
+    for arg in args {
+        total += arg.as_number().map_err(|_| "sum() expects numbers.".to_string())?;
+    }
+    Ok(Value::Number(total))
+}
+
+fn greet(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let name = vm.format_value(args[0]);
+    Ok(vm.intern(&format!("hello, {}", name)))
+}
+
+fn run_with_natives(source: &str) -> (Result<(), InterpretErr>, String) {
+    let buf = SharedBuf::default();
+    let mut vm = VM::new();
+    vm.set_output(buf.clone());
+    vm.define_native("sum", Arity::Variadic, sum);
+    vm.define_native("greet", Arity::Fixed(1), greet);
+    let result = vm.interpret(source);
+    (result, buf.contents())
+}
+
+#[test]
+fn test_builtins() {
+    assert_output("print clock() > 0;", "true\n");
+    assert_output("print clock;", "<native fn>\n");
+    assert_output(r#"print str(1.5) + "!";"#, "1.5!\n");
+}
+
+#[test]
+fn test_host_natives() {
+    let (r, out) = run_with_natives("print sum(); print sum(1, 2, 3); print greet(\"lox\");");
+    assert_eq!(r, Ok(()));
+    assert_eq!(out, "0\n6\nhello, lox\n");
+}
+
+#[test]
+fn test_native_as_value() {
+    let (r, out) = run_with_natives("fun apply(f, x) { return f(x); } print apply(greet, 1);");
+    assert_eq!(r, Ok(()));
+    assert_eq!(out, "hello, 1\n");
+}
+
+#[test]
+fn test_native_errors() {
+    let (r, _) = run("clock(1);");
+    assert_eq!(
+        r,
+        Err(InterpretErr::RuntimeError(
+            "Expected 0 arguments but got 1\n[line 1] in script".to_string()
+        ))
+    );
+    let (r, _) = run_with_natives("fun f() { sum(1, nil); }\nf();");
+    assert_eq!(
+        r,
+        Err(InterpretErr::RuntimeError(
+            "sum() expects numbers.\n[line 1] in f()\n[line 2] in script".to_string()
+        ))
+    );
+}
+
+fn pair(vm: &mut VM, _args: &[Value]) -> Result<Value, String> {
+    let first = vm.intern("first");
+    // 第二次分配会触发回收, `first` 此时只被宿主函数持有
+    vm.intern("second");
+    Ok(first)
+}
+
+#[test]
+fn test_native_temporaries_survive_collection() {
+    let buf = SharedBuf::default();
+    let mut vm = VM::new();
+    vm.set_output(buf.clone());
+    vm.set_stress_gc(true);
+    vm.define_native("pair", Arity::Fixed(0), pair);
+    vm.interpret("print pair(); print pair() + \"!\";").unwrap();
+    assert_eq!(buf.contents(), "first\nfirst!\n");
+}
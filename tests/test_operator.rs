@@ -0,0 +1,228 @@
+mod common;
+use common::run;
+
+/// 每个二元运算符与每种操作数类型的组合, `None` 表示应当产生运行时错误
+const BINARY_CASES: &[(&str, Option<&str>)] = &[
+    ("1 == 1", Some("true")),
+    ("1 == true", Some("false")),
+    ("1 == nil", Some("false")),
+    ("1 == \"a\"", Some("false")),
+    ("true == 1", Some("false")),
+    ("true == true", Some("true")),
+    ("true == nil", Some("false")),
+    ("true == \"a\"", Some("false")),
+    ("nil == 1", Some("false")),
+    ("nil == true", Some("false")),
+    ("nil == nil", Some("true")),
+    ("nil == \"a\"", Some("false")),
+    ("\"a\" == 1", Some("false")),
+    ("\"a\" == true", Some("false")),
+    ("\"a\" == nil", Some("false")),
+    ("\"a\" == \"a\"", Some("true")),
+    ("1 != 1", Some("false")),
+    ("1 != true", Some("true")),
+    ("1 != nil", Some("true")),
+    ("1 != \"a\"", Some("true")),
+    ("true != 1", Some("true")),
+    ("true != true", Some("false")),
+    ("true != nil", Some("true")),
+    ("true != \"a\"", Some("true")),
+    ("nil != 1", Some("true")),
+    ("nil != true", Some("true")),
+    ("nil != nil", Some("false")),
+    ("nil != \"a\"", Some("true")),
+    ("\"a\" != 1", Some("true")),
+    ("\"a\" != true", Some("true")),
+    ("\"a\" != nil", Some("true")),
+    ("\"a\" != \"a\"", Some("false")),
+    ("1 < 1", Some("false")),
+    ("1 < true", None),
+    ("1 < nil", None),
+    ("1 < \"a\"", None),
+    ("true < 1", None),
+    ("true < true", None),
+    ("true < nil", None),
+    ("true < \"a\"", None),
+    ("nil < 1", None),
+    ("nil < true", None),
+    ("nil < nil", None),
+    ("nil < \"a\"", None),
+    ("\"a\" < 1", None),
+    ("\"a\" < true", None),
+    ("\"a\" < nil", None),
+    ("\"a\" < \"a\"", None),
+    ("1 <= 1", Some("true")),
+    ("1 <= true", None),
+    ("1 <= nil", None),
+    ("1 <= \"a\"", None),
+    ("true <= 1", None),
+    ("true <= true", None),
+    ("true <= nil", None),
+    ("true <= \"a\"", None),
+    ("nil <= 1", None),
+    ("nil <= true", None),
+    ("nil <= nil", None),
+    ("nil <= \"a\"", None),
+    ("\"a\" <= 1", None),
+    ("\"a\" <= true", None),
+    ("\"a\" <= nil", None),
+    ("\"a\" <= \"a\"", None),
+    ("1 > 1", Some("false")),
+    ("1 > true", None),
+    ("1 > nil", None),
+    ("1 > \"a\"", None),
+    ("true > 1", None),
+    ("true > true", None),
+    ("true > nil", None),
+    ("true > \"a\"", None),
+    ("nil > 1", None),
+    ("nil > true", None),
+    ("nil > nil", None),
+    ("nil > \"a\"", None),
+    ("\"a\" > 1", None),
+    ("\"a\" > true", None),
+    ("\"a\" > nil", None),
+    ("\"a\" > \"a\"", None),
+    ("1 >= 1", Some("true")),
+    ("1 >= true", None),
+    ("1 >= nil", None),
+    ("1 >= \"a\"", None),
+    ("true >= 1", None),
+    ("true >= true", None),
+    ("true >= nil", None),
+    ("true >= \"a\"", None),
+    ("nil >= 1", None),
+    ("nil >= true", None),
+    ("nil >= nil", None),
+    ("nil >= \"a\"", None),
+    ("\"a\" >= 1", None),
+    ("\"a\" >= true", None),
+    ("\"a\" >= nil", None),
+    ("\"a\" >= \"a\"", None),
+    ("1 + 1", Some("2")),
+    ("1 + true", None),
+    ("1 + nil", None),
+    ("1 + \"a\"", None),
+    ("true + 1", None),
+    ("true + true", None),
+    ("true + nil", None),
+    ("true + \"a\"", None),
+    ("nil + 1", None),
+    ("nil + true", None),
+    ("nil + nil", None),
+    ("nil + \"a\"", None),
+    ("\"a\" + 1", None),
+    ("\"a\" + true", None),
+    ("\"a\" + nil", None),
+    ("\"a\" + \"a\"", Some("aa")),
+    ("1 - 1", Some("0")),
+    ("1 - true", None),
+    ("1 - nil", None),
+    ("1 - \"a\"", None),
+    ("true - 1", None),
+    ("true - true", None),
+    ("true - nil", None),
+    ("true - \"a\"", None),
+    ("nil - 1", None),
+    ("nil - true", None),
+    ("nil - nil", None),
+    ("nil - \"a\"", None),
+    ("\"a\" - 1", None),
+    ("\"a\" - true", None),
+    ("\"a\" - nil", None),
+    ("\"a\" - \"a\"", None),
+    ("1 * 1", Some("1")),
+    ("1 * true", None),
+    ("1 * nil", None),
+    ("1 * \"a\"", None),
+    ("true * 1", None),
+    ("true * true", None),
+    ("true * nil", None),
+    ("true * \"a\"", None),
+    ("nil * 1", None),
+    ("nil * true", None),
+    ("nil * nil", None),
+    ("nil * \"a\"", None),
+    ("\"a\" * 1", None),
+    ("\"a\" * true", None),
+    ("\"a\" * nil", None),
+    ("\"a\" * \"a\"", None),
+    ("1 / 1", Some("1")),
+    ("1 / true", None),
+    ("1 / nil", None),
+    ("1 / \"a\"", None),
+    ("true / 1", None),
+    ("true / true", None),
+    ("true / nil", None),
+    ("true / \"a\"", None),
+    ("nil / 1", None),
+    ("nil / true", None),
+    ("nil / nil", None),
+    ("nil / \"a\"", None),
+    ("\"a\" / 1", None),
+    ("\"a\" / true", None),
+    ("\"a\" / nil", None),
+    ("\"a\" / \"a\"", None),
+    ("1 < 2", Some("true")),
+    ("2 <= 2", Some("true")),
+    ("2 > 1", Some("true")),
+    ("1 >= 2", Some("false")),
+    ("1 == 2", Some("false")),
+    ("1 != 2", Some("true")),
+    ("\"a\" == \"b\"", Some("false")),
+    ("\"a\" != \"b\"", Some("true")),
+    ("\"a\" + \"b\"", Some("ab")),
+    ("true == false", Some("false")),
+    ("false != true", Some("true")),
+    ("7 - 2", Some("5")),
+    ("3 * 4", Some("12")),
+    ("1 / 4", Some("0.25")),
+];
+
+const UNARY_CASES: &[(&str, Option<&str>)] = &[
+    ("!1", Some("false")),
+    ("!0", Some("false")),
+    ("!true", Some("false")),
+    ("!false", Some("true")),
+    ("!nil", Some("true")),
+    ("!\"a\"", Some("false")),
+    ("-1", Some("-1")),
+    ("--2.5", Some("2.5")),
+    ("-true", None),
+    ("-nil", None),
+    ("-\"a\"", None),
+];
+
+fn check(cases: &[(&str, Option<&str>)]) {
+    for (expr, expected) in cases {
+        let (result, output) = run(&format!("print {};", expr));
+        match expected {
+            Some(expected) => {
+                assert!(result.is_ok(), "{} -> {:?}", expr, result);
+                assert_eq!(output, format!("{}\n", expected), "{}", expr);
+            }
+            None => assert!(result.is_err(), "{} should fail, got {}", expr, output),
+        }
+    }
+}
+
+#[test]
+fn test_binary_operators() {
+    check(BINARY_CASES);
+}
+
+#[test]
+fn test_unary_operators() {
+    check(UNARY_CASES);
+}
+
+#[test]
+fn test_grouping() {
+    check(&[
+        ("(1 + 2) * 3", Some("9")),
+        ("2 * (3 + 4) - 1", Some("13")),
+        ("!(1 == 2)", Some("true")),
+        ("((1))", Some("1")),
+        ("(nil == false) == false", Some("true")),
+    ]);
+}
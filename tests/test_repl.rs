@@ -0,0 +1,112 @@
+mod common;
+use common::SharedBuf;
+use lox_vm_rust::{is_complete, Repl, VM};
+
+fn repl() -> (Repl, SharedBuf) {
+    let buf = SharedBuf::default();
+    let mut vm = VM::new();
+    vm.set_output(buf.clone());
+    (Repl::with_vm(vm), buf)
+}
+
+#[test]
+fn test_is_complete() {
+    assert!(is_complete("print 1;"));
+    assert!(is_complete(""));
+    assert!(!is_complete("fun f() {"));
+    assert!(!is_complete("print (1 +"));
+    assert!(!is_complete("var s = \"multi\nline"));
+    assert!(is_complete("fun f() {\n  return 1;\n}\n"));
+    assert!(is_complete("print \"{\";"));
+    // 多余的右括号交给编译器报错, 不再等待输入
+    assert!(is_complete("}"));
+}
+
+#[test]
+fn test_globals_persist() {
+    let (mut repl, buf) = repl();
+    repl.eval("var a = 1;").unwrap();
+    repl.eval("fun inc() { a = a + 1; }").unwrap();
+    repl.eval("inc();\n").unwrap();
+    repl.eval("print a;").unwrap();
+    assert_eq!(buf.contents(), "nil\n2\n");
+}
+
+#[test]
+fn test_bare_expressions_are_printed() {
+    let (mut repl, buf) = repl();
+    repl.eval("1 + 2").unwrap();
+    repl.eval("\"a\" + \"b\";").unwrap();
+    repl.eval("var x = 3;").unwrap();
+    repl.eval("{ x; }").unwrap();
+    repl.eval("x * 2\n").unwrap();
+    assert_eq!(buf.contents(), "3\nab\n6\n");
+}
+
+#[test]
+fn test_errors_do_not_end_session() {
+    let (mut repl, buf) = repl();
+    let compile_error = repl.eval("var = 1;").unwrap_err();
+    assert!(compile_error.starts_with("error[E0002]: expect variable name"));
+    let runtime_error = repl.eval("undefined;").unwrap_err();
+    assert_eq!(runtime_error, "Undefined variable 'undefined'\n[line 1] in script\n");
+    repl.eval("var ok = \"still running\";").unwrap();
+    repl.eval("ok").unwrap();
+    assert_eq!(buf.contents(), "still running\n");
+}
+
+#[test]
+fn test_tokens_command() {
+    let (mut repl, _) = repl();
+    let text = repl.command(":tokens var x = 1;").unwrap();
+    let expected = "   1 TokenVar 'var'\n   1 TokenIdentifier 'x'\n   1 TokenEqual '='\n   1 TokenNumber '1'\n   1 TokenSemicolon ';'\n   1 TokenEof ''\n";
+    assert_eq!(text, expected);
+}
+
+#[test]
+fn test_dis_command_does_not_run() {
+    let (mut repl, buf) = repl();
+    let text = repl.command(":dis fun f() { return 1; } print f();").unwrap();
+    assert!(text.starts_with("== script ==\n"));
+    assert!(text.contains("OPCLOSURE        <fn f>"));
+    assert!(text.contains("== f ==\n"));
+    assert_eq!(buf.contents(), "");
+    // 只编译不执行, f 没有被定义
+    assert!(repl.eval("f").is_err());
+}
+
+#[test]
+fn test_stack_and_time_commands() {
+    let (mut repl, buf) = repl();
+    assert_eq!(repl.command(":stack").unwrap(), "(empty)\n");
+    // 出错时栈上有脚本本身, 局部变量 a 和加法的两个操作数
+    assert!(repl.eval("{ var a = \"x\"; a + nil; }").is_err());
+    assert_eq!(repl.command(":stack").unwrap(), "[ <script> ][ x ][ x ][ nil ]\n");
+    // 下一次执行成功后快照被清空
+    repl.eval("var ok = 1;").unwrap();
+    assert_eq!(repl.command(":stack").unwrap(), "(empty)\n");
+    let text = repl.command(":time var s = 0; for (var i = 0; i < 10; i = i + 1) s = s + i; print s;").unwrap();
+    assert!(text.starts_with("elapsed: "), "{}", text);
+    let count: u64 = text.rsplit(' ').next().unwrap().trim().parse().unwrap();
+    assert!(count > 50);
+    assert_eq!(buf.contents(), "45\n");
+}
+
+#[test]
+fn test_load_command() {
+    let path = std::env::temp_dir().join("lox_repl_load_test.lox");
+    std::fs::write(&path, "var loaded = \"from file\";\nfun twice(x) { return x * 2; }\n").unwrap();
+    let (mut repl, buf) = repl();
+    assert_eq!(repl.command(&format!(":load {}", path.display())).unwrap(), "");
+    repl.eval("loaded").unwrap();
+    repl.eval("twice(21)").unwrap();
+    assert_eq!(buf.contents(), "from file\n42\n");
+    assert!(repl.command(":load /no/such/file.lox").unwrap_err().starts_with("can't read"));
+}
+
+#[test]
+fn test_unknown_command() {
+    let (mut repl, _) = repl();
+    assert_eq!(repl.command(":nope").unwrap_err(), "unknown command ':nope', try :help\n");
+    assert!(repl.command(":help").unwrap().contains(":load <file>"));
+}
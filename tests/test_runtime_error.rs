@@ -0,0 +1,49 @@
+mod common;
+use common::{runtime_error, SharedBuf};
+use lox_vm_rust::{InterpretErr, VM};
+
+#[test]
+fn test_operand_messages() {
+    assert_eq!(runtime_error("1 - nil;"), "Operands must be numbers.\n[line 1] in script");
+    assert_eq!(
+        runtime_error(r#"true + "a";"#),
+        "Operands must be two numbers or two strings.\n[line 1] in script"
+    );
+    assert_eq!(runtime_error("-\"a\";"), "Operand must be a number.\n[line 1] in script");
+}
+
+#[test]
+fn test_undefined_variable_message() {
+    assert_eq!(runtime_error("print x;"), "Undefined variable 'x'\n[line 1] in script");
+}
+
+#[test]
+fn test_error_line() {
+    let source = "var a = 1;\nvar b = \"b\";\n\nprint a * b;";
+    assert_eq!(runtime_error(source), "Operands must be numbers.\n[line 4] in script");
+}
+
+#[test]
+fn test_vm_reusable_after_error() {
+    let buf = SharedBuf::default();
+    let mut vm = VM::new();
+    vm.set_output(buf.clone());
+    assert_eq!(
+        vm.interpret("{ var a = 1; a - nil; }"),
+        Err(InterpretErr::RuntimeError("Operands must be numbers.\n[line 1] in script".to_string()))
+    );
+    // 出错时留在栈上的局部变量已被清空, 不会影响下一次执行
+    assert_eq!(vm.interpret("var b = 2; print b;"), Ok(()));
+    assert_eq!(buf.contents(), "2\n");
+}
+
+#[test]
+fn test_error_line_after_long_constant() {
+    // 行号表按游程存放, 长常量操作数之后的行号依旧准确
+    let mut source = String::new();
+    for i in 0..300 {
+        source += &format!("{};\n", i);
+    }
+    source += "nil - 1;\n";
+    assert_eq!(runtime_error(&source), "Operands must be numbers.\n[line 301] in script");
+}
@@ -1,6 +1,6 @@
 mod common;
 use common::{assert_identifier, assert_number, assert_string, assert_token};
-use lox_vm_rust::{Scanner, TokenType};
+use lox_vm_rust::{ScanError, Scanner, TokenType};
 
 #[test]
 fn test_comment() {
@@ -49,5 +49,10 @@ fn test_eof() {
 #[test]
 fn test_error() {
     let mut scanner = Scanner::new(r#" "12"#);
-    assert_token(&mut scanner, TokenType::TokenError);
+    assert_eq!(scanner.scan_token().error, Some(ScanError::UnterminatedString));
+    let mut scanner = Scanner::new("@");
+    let token = scanner.scan_token();
+    assert_eq!(token.t_type, TokenType::TokenError);
+    assert_eq!(token.error, Some(ScanError::UnexpectedCharacter));
+    assert_eq!(Scanner::new("1").scan_token().error, None);
 }
@@ -0,0 +1,50 @@
+mod common;
+use common::{assert_output, compile_error, runtime_error};
+use lox_vm_rust::ErrorCode;
+
+#[test]
+fn test_block_locals() {
+    assert_output("{ var a = 1; var b = 2; print a + b; }", "3\n");
+    assert_output("{ var a = 1; a = a + 1; print a; }", "2\n");
+}
+
+#[test]
+fn test_shadowing() {
+    let source = r#"
+var a = "global";
+{
+    var a = "outer";
+    {
+        var a = "inner";
+        print a;
+    }
+    print a;
+}
+print a;
+"#;
+    assert_output(source, "inner\nouter\nglobal\n");
+}
+
+#[test]
+fn test_scope_end_pops_locals() {
+    assert_output("{ var a = 1; } var b = 2; print b;", "2\n");
+    assert_eq!(
+        runtime_error("{ var a = 1; } print a;"),
+        "Undefined variable 'a'\n[line 1] in script"
+    );
+}
+
+#[test]
+fn test_redeclaration_in_same_scope() {
+    let d = compile_error("{ var a = 1; var a = 2; }");
+    assert_eq!(d.code, ErrorCode::Redeclaration);
+    assert_eq!(d.message, "already a variable with this name in this scope");
+    assert_output("{ var a = 1; { var a = 2; print a; } }", "2\n");
+}
+
+#[test]
+fn test_read_in_own_initializer() {
+    let d = compile_error("{ var a = 1; { var a = a; } }");
+    assert_eq!(d.code, ErrorCode::UninitializedRead);
+    assert_eq!(d.message, "can't read local variable in its own initializer");
+}
@@ -0,0 +1,21 @@
+mod common;
+use common::{assert_output, compile_error};
+use lox_vm_rust::ErrorCode;
+
+#[test]
+fn test_print() {
+    assert_output("print 4 + 5;", "9\n");
+    assert_output("print 1.5; print nil; print true;", "1.5\nnil\ntrue\n");
+}
+
+#[test]
+fn test_expression_statement() {
+    assert_output("1 + 2;\nprint 3;", "3\n");
+}
+
+#[test]
+fn test_missing_semicolon() {
+    let d = compile_error("print 1");
+    assert_eq!(d.code, ErrorCode::Syntax);
+    assert_eq!(d.message, "expect ';' after value");
+}
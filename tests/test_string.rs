@@ -0,0 +1,27 @@
+mod common;
+use common::{assert_output, run};
+use lox_vm_rust::InterpretErr;
+
+#[test]
+fn test_string_literal() {
+    assert_output(r#"print "hello";"#, "hello\n");
+}
+
+#[test]
+fn test_concatenate() {
+    assert_output(r#"print "hello" + " " + "world";"#, "hello world\n");
+}
+
+#[test]
+fn test_concatenate_mixed_operands() {
+    let (r, _) = run(r#""hello" + 1;"#);
+    assert!(matches!(r, Err(InterpretErr::RuntimeError(_))));
+    let (r, _) = run(r#"1 + "hello";"#);
+    assert!(matches!(r, Err(InterpretErr::RuntimeError(_))));
+}
+
+#[test]
+fn test_interned_equality() {
+    assert_output(r#"print "ab" == "a" + "b";"#, "true\n");
+    assert_output(r#"print "ab" == "ba";"#, "false\n");
+}
@@ -0,0 +1,29 @@
+mod common;
+use common::{assert_output, compile_error, runtime_error};
+use lox_vm_rust::ErrorCode;
+
+#[test]
+fn test_global_define_and_read() {
+    assert_output("var a = 1; var b = a + 2; print b;", "3\n");
+    assert_output("var a; print a;", "nil\n");
+    assert_output(r#"var greeting_1 = "hi"; print greeting_1;"#, "hi\n");
+}
+
+#[test]
+fn test_global_assignment() {
+    assert_output("var a = 1; a = 2; print a;", "2\n");
+    assert_output("var a; var b; a = b = 3; print a + b;", "6\n");
+}
+
+#[test]
+fn test_undefined_variable() {
+    assert_eq!(runtime_error("print a;"), "Undefined variable 'a'\n[line 1] in script");
+    assert_eq!(runtime_error("a = 1;"), "Undefined variable 'a'\n[line 1] in script");
+}
+
+#[test]
+fn test_invalid_assignment_target() {
+    let d = compile_error("var a; var b; var c; a + b = c;");
+    assert_eq!(d.code, ErrorCode::InvalidAssignmentTarget);
+    assert_eq!(d.message, "invalid assignment target");
+}
@@ -0,0 +1,301 @@
+mod common;
+use common::{run, SharedBuf};
+use lox_vm_rust::{InterpretErr, LoxcError, VerifyError, VerifyErrorKind, VM};
+
+// 测试用到的指令编码
+const OPCONSTANT: u8 = 0;
+const OPNIL: u8 = 2;
+const OPTRUE: u8 = 3;
+const OPRETURN: u8 = 5;
+const OPPRINT: u8 = 15;
+const OPPOP: u8 = 16;
+const OPGET_GLOBAL: u8 = 18;
+const OPGET_LOCAL: u8 = 20;
+const OPJUMP: u8 = 22;
+const OPJUMP_IF_FALSE: u8 = 23;
+const OPLOOP: u8 = 24;
+const OPCLOSURE: u8 = 26;
+const OPGET_UPVALUE: u8 = 27;
+const OPCLOSE_UPVALUE: u8 = 29;
+const OPCLASS: u8 = 30;
+const OPMETHOD: u8 = 33;
+const OPINHERIT: u8 = 35;
+const OPGET_SUPER: u8 = 36;
+
+enum Constant {
+    Number(f64),
+    Str(&'static str),
+    Function(Function),
+}
+
+/// 手工拼出的函数, 所有指令位于第 1 行
+struct Function {
+    name: Option<&'static str>,
+    arity: u8,
+    upvalue_count: u16,
+    code: Vec<u8>,
+    constants: Vec<Constant>,
+}
+
+impl Function {
+    fn script(code: &[u8], constants: Vec<Constant>) -> Self {
+        Function {
+            name: None,
+            arity: 0,
+            upvalue_count: 0,
+            code: code.to_vec(),
+            constants,
+        }
+    }
+    fn write(&self, bytes: &mut Vec<u8>) {
+        let string = |bytes: &mut Vec<u8>, s: &str| {
+            bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(s.as_bytes());
+        };
+        match self.name {
+            Some(name) => {
+                bytes.push(1);
+                string(bytes, name);
+            }
+            None => bytes.push(0),
+        }
+        bytes.push(self.arity);
+        bytes.extend_from_slice(&self.upvalue_count.to_le_bytes());
+        bytes.extend_from_slice(&(self.code.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.code);
+        let runs: &[[u32; 2]] = if self.code.is_empty() { &[] } else { &[[0, 1]] };
+        bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        for [start, line] in runs {
+            bytes.extend_from_slice(&start.to_le_bytes());
+            bytes.extend_from_slice(&line.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.constants.len() as u32).to_le_bytes());
+        for constant in &self.constants {
+            match constant {
+                Constant::Number(n) => {
+                    bytes.push(0);
+                    bytes.extend_from_slice(&n.to_le_bytes());
+                }
+                Constant::Str(s) => {
+                    bytes.push(1);
+                    string(bytes, s);
+                }
+                Constant::Function(f) => {
+                    bytes.push(2);
+                    f.write(bytes);
+                }
+            }
+        }
+    }
+}
+
+/// 把 `function` 作为顶层代码写成 `.loxc` 文件
+fn file(function: &Function) -> Vec<u8> {
+    let mut bytes = b"LOXC".to_vec();
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    function.write(&mut bytes);
+    bytes
+}
+
+/// 只含顶层代码, 常量都是数字的 `.loxc` 文件
+fn loxc(code: &[u8], constants: &[f64]) -> Vec<u8> {
+    file(&Function::script(code, constants.iter().map(|n| Constant::Number(*n)).collect()))
+}
+
+fn verify_file(bytes: &[u8]) -> Result<(), VerifyError> {
+    match VM::new().load_loxc(bytes) {
+        Ok(_) => Ok(()),
+        Err(LoxcError::InvalidBytecode(e)) => Err(e),
+        Err(e) => panic!("expected a verify error, got {:?}", e),
+    }
+}
+
+fn verify(code: &[u8], constants: &[f64]) -> Result<(), VerifyError> {
+    verify_file(&loxc(code, constants))
+}
+
+fn rejected(code: &[u8], constants: &[f64]) -> (usize, VerifyErrorKind) {
+    let e = verify(code, constants).unwrap_err();
+    assert_eq!(e.function, "script");
+    (e.offset, e.kind)
+}
+
+#[test]
+fn test_valid_bytecode_runs() {
+    let bytes = loxc(&[OPCONSTANT, 0, OPPRINT, OPNIL, OPRETURN], &[42.0]);
+    let mut vm = VM::new();
+    let buf = SharedBuf::default();
+    vm.set_output(buf.clone());
+    let script = vm.load_loxc(&bytes).unwrap();
+    vm.run_function(&script).unwrap();
+    assert_eq!(buf.contents(), "42\n");
+}
+
+#[test]
+fn test_operands() {
+    assert_eq!(rejected(&[200], &[]), (0, VerifyErrorKind::InvalidOpCode(200)));
+    assert_eq!(rejected(&[OPNIL, OPCONSTANT], &[]), (2, VerifyErrorKind::TruncatedInstruction));
+    assert_eq!(
+        rejected(&[OPCONSTANT, 3, OPRETURN], &[1.0]),
+        (0, VerifyErrorKind::ConstantOutOfRange(3))
+    );
+    assert_eq!(
+        rejected(&[OPGET_GLOBAL, 0, OPRETURN], &[1.0]),
+        (0, VerifyErrorKind::ExpectedString(0))
+    );
+    assert_eq!(
+        rejected(&[OPGET_LOCAL, 1, OPRETURN], &[]),
+        (0, VerifyErrorKind::LocalOutOfRange(1))
+    );
+    assert_eq!(
+        rejected(&[OPGET_UPVALUE, 0, OPRETURN], &[]),
+        (0, VerifyErrorKind::UpvalueOutOfRange(0))
+    );
+}
+
+#[test]
+fn test_jump_targets() {
+    // 跳到 OPCONSTANT 的操作数上
+    assert_eq!(
+        rejected(&[OPJUMP, 0, 1, OPCONSTANT, 0, OPRETURN], &[1.0]),
+        (0, VerifyErrorKind::InvalidJumpTarget)
+    );
+    assert_eq!(
+        rejected(&[OPJUMP, 0, 9, OPNIL, OPRETURN], &[]),
+        (0, VerifyErrorKind::InvalidJumpTarget)
+    );
+    assert_eq!(
+        rejected(&[OPNIL, OPLOOP, 0, 9, OPRETURN], &[]),
+        (1, VerifyErrorKind::InvalidJumpTarget)
+    );
+}
+
+#[test]
+fn test_stack_depth() {
+    // 进入顶层代码时栈上只有它自己
+    assert_eq!(rejected(&[OPPOP, OPPOP, OPNIL, OPRETURN], &[]), (1, VerifyErrorKind::StackUnderflow));
+    // 条件成立的路径多压了一个 nil
+    assert_eq!(
+        rejected(&[OPTRUE, OPJUMP_IF_FALSE, 0, 1, OPNIL, OPRETURN], &[]),
+        (5, VerifyErrorKind::StackMismatch { expected: 2, found: 3 })
+    );
+    // 每轮循环都多压一个值
+    assert_eq!(
+        rejected(&[OPNIL, OPLOOP, 0, 4], &[]),
+        (0, VerifyErrorKind::StackMismatch { expected: 1, found: 2 })
+    );
+}
+
+#[test]
+fn test_falls_off_end() {
+    assert_eq!(rejected(&[], &[]), (0, VerifyErrorKind::FallsOffEnd));
+    assert_eq!(rejected(&[OPNIL, OPPRINT], &[]), (1, VerifyErrorKind::FallsOffEnd));
+    assert!(verify(&[OPNIL, OPRETURN, 200], &[]).is_err());
+}
+
+#[test]
+fn test_compiled_code_verifies() {
+    let source = r#"
+    {
+      fun countdown(n) {
+        if (n > 0) countdown(n - 1);
+        else print "done";
+      }
+      countdown(3);
+    }
+    class A { f() { return 1; } }
+    class B < A { f() { return super.f() + 1; } }
+    for (var i = 0; i < 2; i = i + 1) { print B().f() and i or nil; }
+    "#;
+    let mut vm = VM::new();
+    let script = vm.compile(source).unwrap();
+    assert_eq!(vm.verify(&script), Ok(()));
+    assert!(run(source).0.is_ok());
+}
+
+#[test]
+fn test_error_message() {
+    let e = verify(&[OPPOP, OPPOP, OPNIL, OPRETURN], &[]).unwrap_err();
+    assert_eq!(e.to_string(), "invalid bytecode in script at 0001: stack underflow");
+    let e = VM::new().load_loxc(&loxc(&[200], &[])).unwrap_err();
+    assert_eq!(e.to_string(), "invalid bytecode in script at 0000: unknown opcode 200");
+}
+
+/// 通过校验的字节码在运行时遇到类型不符的操作数, 应当报错而不是崩溃
+fn runtime_error(function: &Function) -> String {
+    let mut vm = VM::new();
+    let script = vm.load_loxc(&file(function)).unwrap();
+    match vm.run_function(&script) {
+        Err(InterpretErr::RuntimeError(message)) => message,
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_operand_types_checked_at_runtime() {
+    let method = |code: &[u8]| {
+        let f = Function {
+            name: Some("x"),
+            ..Function::script(&[OPNIL, OPRETURN], vec![])
+        };
+        Function::script(code, vec![Constant::Str("x"), Constant::Function(f)])
+    };
+    assert_eq!(
+        runtime_error(&method(&[OPNIL, OPNIL, OPMETHOD, 0, OPPOP, OPNIL, OPRETURN])),
+        "Methods can only be added to classes.\n[line 1] in script"
+    );
+    assert_eq!(
+        runtime_error(&method(&[OPNIL, OPNIL, OPGET_SUPER, 0, OPRETURN])),
+        "Superclass must be a class.\n[line 1] in script"
+    );
+    // 父类是类, 子类是 nil
+    assert_eq!(
+        runtime_error(&method(&[OPCLASS, 0, OPNIL, OPINHERIT, OPRETURN])),
+        "Subclass must be a class.\n[line 1] in script"
+    );
+    // 方法表中只能放闭包, 这里的方法是 nil
+    assert_eq!(
+        runtime_error(&method(&[OPCLASS, 0, OPNIL, OPMETHOD, 0, OPRETURN])),
+        "Method must be a function.\n[line 1] in script"
+    );
+}
+
+#[test]
+fn test_popped_captured_local() {
+    let script = |code: &[u8]| {
+        let g = Function {
+            name: Some("g"),
+            upvalue_count: 1,
+            ..Function::script(&[OPNIL, OPRETURN], vec![])
+        };
+        Function::script(code, vec![Constant::Function(g)])
+    };
+    let e = verify_file(&file(&script(&[OPNIL, OPCLOSURE, 0, 0, 1, 1, 1, OPPOP, OPPOP, OPNIL, OPRETURN]))).unwrap_err();
+    assert_eq!((e.offset, e.kind), (8, VerifyErrorKind::CapturedLocalPopped(1)));
+    // 先关闭 upvalue 再出栈
+    assert_eq!(
+        verify_file(&file(&script(&[OPNIL, OPCLOSURE, 0, 0, 1, 1, 1, OPPOP, OPCLOSE_UPVALUE, OPNIL, OPRETURN]))),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_script_signature() {
+    let script = Function {
+        upvalue_count: 1,
+        ..Function::script(&[OPGET_UPVALUE, 0, OPRETURN], vec![])
+    };
+    let e = verify_file(&file(&script)).unwrap_err();
+    assert_eq!(
+        (e.offset, e.kind),
+        (0, VerifyErrorKind::InvalidScript { arity: 0, upvalue_count: 1 })
+    );
+    let script = Function {
+        arity: 1,
+        ..Function::script(&[OPNIL, OPRETURN], vec![])
+    };
+    assert_eq!(
+        verify_file(&file(&script)).unwrap_err().to_string(),
+        "invalid bytecode in script at 0000: top-level code can't have parameters or upvalues (has 1 and 0)"
+    );
+}